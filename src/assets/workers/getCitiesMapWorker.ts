@@ -3,7 +3,7 @@ import type { SingleMapOption, MultiMapOption, Settings, Extent, ProgressData, R
 import type { MapProcessWorkerType } from '~/assets/workers/mapProcessWorker'
 import { mapSpec, PIXELS_PER_TILE, PIXELS_PER_VECTOR_TILE } from '~/utils/const'
 import { getMinMaxHeight } from '~/utils/elevation'
-import { mergeTiles, scaleDownWorldMap } from '~/utils/tileProcess'
+import { mergeTiles, scaleDownWorldMap, splitTileOffset } from '~/utils/tileProcess'
 import { getExtentInWorldCoords, rotateExtent } from '~/utils/getExtent'
 import initPng, { encode_png } from '~~/wasm/png_lib/pkg'
 import { WorkerPool } from '~/utils/workerPool'
@@ -120,6 +120,8 @@ class GetCitiesMapWorker {
 
     this.validateArrayElements(heightmaps, 'generateSingleMap: Error when getting heightmap data')
 
+    const mapSize = Math.sqrt(data!.heightmap!.length)
+
     const tileMaps: (Float32Array[] | undefined)[] = option.division > 1
       ? await Promise.all(
         heightmaps.map(async (map) => {
@@ -141,7 +143,8 @@ class GetCitiesMapWorker {
         const subWorker = await this.workerPool?.getWorker()
         if (!subWorker || !map) return undefined
 
-        const tileMap = await subWorker.remote.applyEffects(Comlink.transfer(map!, [map!.buffer]), mapOption)
+        const [offsetX, offsetY] = splitTileOffset(mapSize, option.division, 100, index)
+        const tileMap = await subWorker.remote.applyEffects(Comlink.transfer(map!, [map!.buffer]), mapOption, offsetX, offsetY)
         const noiseMap = tileMap!.noiseMap ? Comlink.transfer(tileMap!.noiseMap, [tileMap!.noiseMap!.buffer]) : undefined
         const combinedMap = await subWorker.remote.combineMap(
           Comlink.transfer(tileMap!.effectedMap, [tileMap!.effectedMap.buffer]),
@@ -264,6 +267,8 @@ class GetCitiesMapWorker {
 
     this.validateArrayElements(tmpMaps, 'generateCS2Map: Error when processing tiles')
 
+    const mapSize = Math.sqrt(tmpMaps[0]!.length)
+
     const tileMaps = await Promise.all(
       tmpMaps.map(async (map) => {
         const subWorker = await this.workerPool?.getWorker()
@@ -283,7 +288,8 @@ class GetCitiesMapWorker {
         const subWorker = await this.workerPool?.getWorker()
         if (!subWorker || !map) return undefined
 
-        const tileMap = await subWorker.remote.applyEffects(Comlink.transfer(map!, [map!.buffer]), mapOption)
+        const [offsetX, offsetY] = splitTileOffset(mapSize, division, 100, index)
+        const tileMap = await subWorker.remote.applyEffects(Comlink.transfer(map!, [map!.buffer]), mapOption, offsetX, offsetY)
         const noiseMap = tileMap!.noiseMap ? Comlink.transfer(tileMap!.noiseMap, [tileMap!.noiseMap!.buffer]) : undefined
         const combinedMap = await subWorker.remote.combineMap(
           Comlink.transfer(tileMap!.effectedMap, [tileMap!.effectedMap.buffer]),
@@ -320,7 +326,7 @@ class GetCitiesMapWorker {
     this.workerPool?.releaseWorker(worker1!)
     this.workerPool?.releaseWorker(worker2!)
 
-    const resultWorldMap = scaleDownWorldMap(tmpWorldMap!)
+    const resultWorldMap = await scaleDownWorldMap(tmpWorldMap!)
 
     const [h_minmax, w_minmax] = await Promise.all([
       getMinMaxHeight(resultHeightmap!),
@@ -101,24 +101,27 @@ class MapProcessWorker {
     }
   }
 
-  public async applyEffects(mapData: Float32Array, option: MapOption) {
+  // (offsetX, offsetY) is the position of the tile in the whole map, so the noise continues across tiles
+  public async applyEffects(mapData: Float32Array, option: MapOption, offsetX = 0, offsetY = 0) {
     this.validateCallback()
 
     try {
       this.progressCallback!({ type: 'phase', data: `Processing effects (#${this.index})` })
 
       const { settings, smoothRadius, sharpenRadius, unitSize } = option
+      const width = Math.sqrt(mapData.length)
 
+      // Smoothing fades out below smthThres, sharpening and noise fade in above shrpThres
       const blurredMap = settings.smoothing > 0
-        ? await gaussianBlur(mapData, smoothRadius, settings.smoothing / 100, settings.smthThres, settings.smthFade) ?? mapData
+        ? await gaussianBlur(mapData, smoothRadius, settings.smoothing / 100, -Infinity, settings.smthThres - settings.smthFade, 0, settings.smthFade) ?? mapData
         : mapData
 
       const effectedMap = settings.sharpen > 0
-        ? await unsharpMask(blurredMap, settings.sharpen / 100, sharpenRadius, settings.shrpThres, settings.shrpFade) ?? blurredMap
+        ? await unsharpMask(blurredMap, settings.sharpen / 100, sharpenRadius, settings.shrpThres + settings.shrpFade, Infinity, settings.shrpFade, 0) ?? blurredMap
         : blurredMap
 
       const noiseMap = settings.noise > 0
-        ? await noise(effectedMap, settings.noise, settings.noiseThres, unitSize, settings.shrpThres, settings.shrpFade)
+        ? await noise(effectedMap, settings.noise, settings.noiseThres, unitSize, settings.shrpThres + settings.shrpFade, Infinity, settings.shrpFade, 0, 'linear', width, width, 'replicate', 0, settings.noiseSeed ?? 0, offsetX, offsetY)
         : undefined
 
       const transferables: (ArrayBufferLike | ImageBitmap)[] = [effectedMap.buffer]
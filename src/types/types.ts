@@ -32,6 +32,10 @@ const depthPointSchema = z.object({
 export type LngLat = Extract<LngLatLike, [number, number]>
 export type HeightCalcType = 'manual' | 'limit' | 'maximize'
 export type Interpolation = 'bilinear' | 'bicubic'
+export type BorderMode = 'replicate' | 'reflect' | 'wrap' | 'constant'
+export type CoherentNoiseType = 'perlin' | 'simplex' | 'ridged' | 'billow' | 'worley'
+export type MaskMode = 'multiply' | 'replace'
+export type FadeShape = 'linear' | 'smoothstep' | 'smootherstep' | 'cosine'
 export type MapType = 'cs1' | 'cs2' | 'cs2play' | 'unity' | 'ue' | 'ocean'
 export type StyleType = Record<'label' | 'value' | 'before' | 'grid' | 'alpha', string>
 
@@ -134,6 +138,7 @@ export const settingsSchema = z.object({
   interpolation: interpolationSchema,
   noise: z.number(),
   noiseThres: z.number(),
+  noiseSeed: z.number().optional(),
   displayEffectArea: z.boolean(),
   applyEffectAmount: z.boolean(),
   normalizePreview: z.boolean(),
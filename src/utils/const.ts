@@ -175,6 +175,7 @@ export const initialValue: Settings = {
   interpolation: 'bicubic',
   noise: 0,
   noiseThres: 0,
+  noiseSeed: 0,
   displayEffectArea: false,
   applyEffectAmount: false,
   normalizePreview: false,
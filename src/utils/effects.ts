@@ -1,128 +1,236 @@
-import init, { allocate_memory, free_memory, gaussian_blur, unsharp_mask, noise as gen_noise } from '~~/wasm/effects_lib/pkg'
-
-export const gaussianBlur = async (data: Float32Array, radius: number, blend: number, threshold: number, fade: number) => {
-  let inputPtr: number | undefined
-  let outputPtr: number | undefined
-  const instance = await init()
+import init, { HeightBuffer, EffectMask, gaussian_blur, unsharp_mask, noise as gen_noise, coherent_noise, hillshade, elevation_mask, slope_mask, water_mask } from '~~/wasm/effects_lib/pkg'
+import type { BorderMode, CoherentNoiseType, FadeShape, MaskMode } from '~/types/types'
+
+// The effects use the free functions rather than a long-lived EffectsContext. Every map process worker
+// is replaced after one tile to give back its wasm memory, which never shrinks, so a cache kept here
+// would never see a second tile.
+
+// Optional mask of an effect, built by the mask builders below and freed by the caller.
+// It can be passed to any number of effects on data of the same size.
+// Without a mask, the effects get EffectMask.none(), created once after init.
+let noMask: EffectMask | undefined
+const getNoMask = () => (noMask ??= EffectMask.none())
+
+// The effects apply to elevations in [low, high], fading out over fadeLow (m) below and fadeHigh (m) above it.
+// An infinite low or high leaves that side open.
+export const gaussianBlur = async (data: Float32Array, radius: number, blend: number, low: number, high: number, fadeLow: number, fadeHigh: number, shape: FadeShape = 'linear', width = Math.sqrt(data.length), height = width, border: BorderMode = 'replicate', borderValue = 0, mask?: EffectMask) => {
+  let input: HeightBuffer | undefined
+  let output: HeightBuffer | undefined
+  await init()
 
   try {
-    const result = new Float32Array(data.length)
-    inputPtr = allocate_memory(data.length)
-    outputPtr = allocate_memory(data.length)
-
-    const input = new Float32Array(
-      instance.memory.buffer,
-      inputPtr,
-      data.length,
-    )
-    input.set(data)
-
-    gaussian_blur(inputPtr, outputPtr, data.length, radius, blend, threshold, fade)
-
-    const output = new Float32Array(
-      instance.memory.buffer,
-      outputPtr,
-      data.length,
-    )
-    result.set(output)
-
-    return result
+    input = HeightBuffer.from_data(width, height, data)
+    output = new HeightBuffer(width, height)
+
+    gaussian_blur(input, output, radius, blend, low, high, fadeLow, fadeHigh, shape, border, borderValue, mask ?? getNoMask())
+
+    return output.view().slice()
   } catch (error) {
     console.error(error)
   } finally {
-    if (inputPtr !== undefined) {
-      free_memory(inputPtr, data.length)
-    }
-    if (outputPtr !== undefined) {
-      free_memory(outputPtr, data.length)
-    }
+    input?.free()
+    output?.free()
   }
 }
 
 
-export const unsharpMask = async (data: Float32Array, amount: number, radius: number, threshold: number, fade: number) => {
-  let inputPtr: number | undefined
-  let blurredPtr: number | undefined
-  let outputPtr: number | undefined
-  const instance = await init()
+export const unsharpMask = async (data: Float32Array, amount: number, radius: number, low: number, high: number, fadeLow: number, fadeHigh: number, shape: FadeShape = 'linear', width = Math.sqrt(data.length), height = width, border: BorderMode = 'replicate', borderValue = 0, mask?: EffectMask) => {
+  let input: HeightBuffer | undefined
+  let blurred: HeightBuffer | undefined
+  let output: HeightBuffer | undefined
+  await init()
 
   try {
-    const result = new Float32Array(data.length)
-    inputPtr = allocate_memory(data.length)
-    blurredPtr = allocate_memory(data.length)
-    outputPtr = allocate_memory(data.length)
-
-    const input = new Float32Array(
-      instance.memory.buffer,
-      inputPtr,
-      data.length,
-    )
-    input.set(data)
-
-    gaussian_blur(inputPtr, blurredPtr, data.length, radius, 1, 100000, 0)
-    unsharp_mask(inputPtr, blurredPtr, outputPtr, data.length, amount, threshold, fade)
-
-    const output = new Float32Array(
-      instance.memory.buffer,
-      outputPtr,
-      data.length,
-    )
-    result.set(output)
-
-    return result
+    input = HeightBuffer.from_data(width, height, data)
+    blurred = new HeightBuffer(width, height)
+    output = new HeightBuffer(width, height)
+
+    gaussian_blur(input, blurred, radius, 1, -Infinity, Infinity, 0, 0, 'linear', border, borderValue, getNoMask())
+    unsharp_mask(input, blurred, output, amount, low, high, fadeLow, fadeHigh, shape, mask ?? getNoMask())
+
+    return output.view().slice()
   } catch (error) {
     console.error(error)
   } finally {
-    if (inputPtr !== undefined) {
-      free_memory(inputPtr, data.length)
-    }
-    if (blurredPtr !== undefined) {
-      free_memory(blurredPtr, data.length)
-    }
-    if (outputPtr !== undefined) {
-      free_memory(outputPtr, data.length)
-    }
+    input?.free()
+    blurred?.free()
+    output?.free()
   }
 }
 
 
 // amount and unitSize are specified in m scale
-export const noise = async (data: Float32Array, amount: number, tri: number, unitSize: number, threshold: number, fade: number) => {
-  let inputPtr: number | undefined
-  let outputPtr: number | undefined
-  const instance = await init()
+// (offsetX, offsetY) is the position of the data in the whole map, so tiles get seamless noise
+export const noise = async (data: Float32Array, amount: number, tri: number, unitSize: number, low: number, high: number, fadeLow: number, fadeHigh: number, shape: FadeShape = 'linear', width = Math.sqrt(data.length), height = width, border: BorderMode = 'replicate', borderValue = 0, seed = 0, offsetX = 0, offsetY = 0, mask?: EffectMask) => {
+  let input: HeightBuffer | undefined
+  let output: HeightBuffer | undefined
+  await init()
 
   try {
-    const result = new Float32Array(data.length)
-    inputPtr = allocate_memory(data.length)
-    outputPtr = allocate_memory(data.length)
-
-    const input = new Float32Array(
-      instance.memory.buffer,
-      inputPtr,
-      data.length,
-    )
-    input.set(data)
-
-    gen_noise(inputPtr, outputPtr, data.length, amount, tri, unitSize, threshold, fade)
-
-    const output = new Float32Array(
-      instance.memory.buffer,
-      outputPtr,
-      data.length,
-    )
-    result.set(output)
-
-    return result
+    input = HeightBuffer.from_data(width, height, data)
+    output = new HeightBuffer(width, height)
+
+    gen_noise(input, output, amount, tri, unitSize, low, high, fadeLow, fadeHigh, shape, seed, offsetX, offsetY, border, borderValue, mask ?? getNoMask())
+
+    return output.view().slice()
   } catch (error) {
     console.error(error)
   } finally {
-    if (inputPtr !== undefined) {
-      free_memory(inputPtr, data.length)
-    }
-    if (outputPtr !== undefined) {
-      free_memory(outputPtr, data.length)
-    }
+    input?.free()
+    output?.free()
   }
 }
 
+
+// amount, unitSize and wavelength are specified in m scale
+export const coherentNoise = async (
+  data: Float32Array,
+  amount: number,
+  tri: number,
+  unitSize: number,
+  low: number,
+  high: number,
+  fadeLow: number,
+  fadeHigh: number,
+  shape: FadeShape = 'linear',
+  type: CoherentNoiseType = 'perlin',
+  wavelength = 1000,
+  octaves = 6,
+  lacunarity = 2,
+  gain = 0.5,
+  width = Math.sqrt(data.length),
+  height = width,
+  border: BorderMode = 'replicate',
+  borderValue = 0,
+  seed = 0,
+  offsetX = 0,
+  offsetY = 0,
+  mask?: EffectMask,
+) => {
+  let input: HeightBuffer | undefined
+  let output: HeightBuffer | undefined
+  await init()
+
+  try {
+    input = HeightBuffer.from_data(width, height, data)
+    output = new HeightBuffer(width, height)
+
+    coherent_noise(input, output, amount, tri, unitSize, low, high, fadeLow, fadeHigh, shape, type, wavelength, octaves, lacunarity, gain, seed, offsetX, offsetY, border, borderValue, mask ?? getNoMask())
+
+    return output.view().slice()
+  } catch (error) {
+    console.error(error)
+  } finally {
+    input?.free()
+    output?.free()
+  }
+}
+
+
+// Shaded relief preview. unitSize is specified in m scale, tintMin and tintMax are elevations.
+export const renderHillshade = async (
+  data: Float32Array,
+  unitSize: number,
+  exaggeration = 1,
+  mode: 'single' | 'multidirectional' = 'multidirectional',
+  azimuth = 315,
+  altitude = 45,
+  tintBlend = 0,
+  tintMin = 0,
+  tintMax = 1024,
+  width = Math.sqrt(data.length),
+  height = width,
+  border: BorderMode = 'replicate',
+  borderValue = 0,
+) => {
+  let input: HeightBuffer | undefined
+  await init()
+
+  try {
+    input = HeightBuffer.from_data(width, height, data)
+    const rgba = hillshade(input, unitSize, exaggeration, mode, azimuth, altitude, tintBlend, tintMin, tintMax, border, borderValue)
+
+    return new ImageData(new Uint8ClampedArray(rgba.buffer, rgba.byteOffset, rgba.length), width, height)
+  } catch (error) {
+    console.error(error)
+  } finally {
+    input?.free()
+  }
+}
+
+
+// Mask builders for the effects above. Each returns an EffectMask owning the mask buffer, which the caller frees.
+// mode 'multiply' scales the elevation band of an effect by the mask, 'replace' uses the mask instead of it.
+
+// 1 where the elevation is in [low, high], fading out over fadeLow (m) below and fadeHigh (m) above the range.
+// The effects take an elevation band themselves, so use it with mode 'multiply' to combine a second band.
+export const elevationMask = async (data: Float32Array, low: number, high: number, fadeLow: number, fadeHigh = fadeLow, shape: FadeShape = 'linear', width = Math.sqrt(data.length), height = width, mode: MaskMode = 'multiply') => {
+  let input: HeightBuffer | undefined
+  let output: HeightBuffer | undefined
+  await init()
+
+  try {
+    input = HeightBuffer.from_data(width, height, data)
+    output = new HeightBuffer(width, height)
+
+    elevation_mask(input, output, low, high, fadeLow, fadeHigh, shape)
+
+    const mask = new EffectMask(output, mode)
+    output = undefined
+    return mask
+  } catch (error) {
+    console.error(error)
+  } finally {
+    input?.free()
+    output?.free()
+  }
+}
+
+
+// 1 where the slope is in [low, high] degrees, fading out linearly over fade (degrees). unitSize is specified in m scale.
+export const slopeMask = async (data: Float32Array, unitSize: number, low: number, high: number, fade: number, width = Math.sqrt(data.length), height = width, border: BorderMode = 'replicate', borderValue = 0, mode: MaskMode = 'multiply') => {
+  let input: HeightBuffer | undefined
+  let output: HeightBuffer | undefined
+  await init()
+
+  try {
+    input = HeightBuffer.from_data(width, height, data)
+    output = new HeightBuffer(width, height)
+
+    slope_mask(input, output, unitSize, low, high, fade, border, borderValue)
+
+    const mask = new EffectMask(output, mode)
+    output = undefined
+    return mask
+  } catch (error) {
+    console.error(error)
+  } finally {
+    input?.free()
+    output?.free()
+  }
+}
+
+
+// 1 on land within distance pixels of water, fading out linearly over fade pixels.
+// waterMap is 1 on land and 0 on water, like the decoded water map.
+export const waterMask = async (waterMap: Float32Array, distance: number, fade: number, includeWater = false, width = Math.sqrt(waterMap.length), height = width, mode: MaskMode = 'multiply') => {
+  let input: HeightBuffer | undefined
+  let output: HeightBuffer | undefined
+  await init()
+
+  try {
+    input = HeightBuffer.from_data(width, height, waterMap)
+    output = new HeightBuffer(width, height)
+
+    water_mask(input, output, distance, fade, includeWater)
+
+    const mask = new EffectMask(output, mode)
+    output = undefined
+    return mask
+  } catch (error) {
+    console.error(error)
+  } finally {
+    input?.free()
+    output?.free()
+  }
+}
@@ -1,4 +1,5 @@
-import init, { allocate_memory, free_memory, subdivide_by_gradient } from '~~/wasm/tiles_lib/pkg'
+import init, { HeightBuffer, subdivide_by_gradient } from '~~/wasm/tiles_lib/pkg'
+import type { BorderMode } from '~/types/types'
 
 const addPadding = (data: Float32Array, padding: number) => {
   const size = Math.sqrt(data.length)
@@ -160,29 +161,31 @@ export const subdivideByGradient = (elevation: Float32Array, blurKernel: number[
   return recursiveSubdivide(elevation, count)
 }
 
-export const subdivideByGradientInWasm = async (elevation: Float32Array, blurKernel: number[], count = 1) => {
-  const instance = await init()
-
-  const elevationPtr = allocate_memory(elevation.length)
-  const elevationMemory = new Float32Array(instance.memory.buffer, elevationPtr, elevation.length)
-  elevationMemory.set(elevation)
-
+export const subdivideByGradientInWasm = async (
+  elevation: Float32Array,
+  blurKernel: number[],
+  count = 1,
+  seed = 0,
+  roughness = 0.1259,
+  distribution: 'uniform' | 'gaussian' | 'none' = 'uniform',
+  decay = 1,
+  width = Math.sqrt(elevation.length),
+  height = width,
+  border: BorderMode = 'replicate',
+  borderValue = 0,
+) => {
+  await init()
+
+  const input = HeightBuffer.from_data(width, height, elevation)
   const kernel = new Float32Array(blurKernel)
 
-  const resultPtr = subdivide_by_gradient(elevationPtr, elevation.length, kernel, count)
-  const resultLen = elevation.length * Math.pow(2, count * 2)
-
-  const result = new Float32Array(resultLen)
-
-  const resultMemory = new Float32Array(
-    instance.memory.buffer,
-    resultPtr,
-    resultLen,
-  )
-  result.set(resultMemory)
+  try {
+    const output = subdivide_by_gradient(input, kernel, count, seed, 0, 0, roughness, distribution, decay, border, borderValue)
+    const result = output.view().slice()
+    output.free()
 
-  free_memory(elevationPtr, elevation.length)
-  free_memory(resultPtr, resultLen)
-
-  return result
+    return result
+  } finally {
+    input.free()
+  }
 }
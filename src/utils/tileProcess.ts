@@ -1,208 +1,124 @@
-import init, { allocate_memory, free_memory, scale_up_bicubic, type InitOutput } from '~~/wasm/tiles_lib/pkg'
+import init, { HeightBuffer, scale_up_size, scale_up_rows, scale_down_size, scale_down_source_rows, scale_down_rows, blend_maps } from '~~/wasm/tiles_lib/pkg'
+import type { BorderMode } from '~/types/types'
 
-export const scaleUpBicubic = async (data: Float32Array) => {
-  let inputPtr: number | undefined
-  let outputPtr: number | undefined
-  let instance: InitOutput | null = await init()
+// Output rows per scale_up_rows call. Only the input and one strip live in wasm memory at a time.
+const SCALE_UP_STRIP_HEIGHT = 512
 
-  const originalSize = 4096
-  const padding = 100
-  const scale = 4
-  const newSize = originalSize * scale
-  const outputSize = newSize + 2 * padding
-  const outputLength = outputSize * outputSize
+// Output rows per scale_down_rows call. Only the source rows of one strip live in wasm memory at a time.
+const SCALE_DOWN_STRIP_HEIGHT = 256
+
+export const scaleUpBicubic = async (data: Float32Array, width = 4096, height = 4096, padding = 100, scale = 4, monotone = false) => {
+  let input: HeightBuffer | undefined
+  let strip: HeightBuffer | undefined
+  await init()
 
   try {
-    inputPtr = allocate_memory(data.length)
-    outputPtr = allocate_memory(outputLength)
-    const result = new Float32Array(outputLength)
-
-    const input = new Float32Array(
-      instance.memory.buffer,
-      inputPtr,
-      data.length,
-    )
-    input.set(data)
-
-    scale_up_bicubic(inputPtr, outputPtr)
-
-    const output = new Float32Array(
-      instance.memory.buffer,
-      outputPtr,
-      outputLength,
-    )
-    result.set(output)
+    input = HeightBuffer.from_data(width + 2 * padding, height + 2 * padding, data)
+    const [outputWidth, outputHeight] = scale_up_size(width + 2 * padding, height + 2 * padding, padding, scale)
+    const result = new Float32Array(outputWidth * outputHeight)
+
+    for (let y0 = 0; y0 < outputHeight; y0 += SCALE_UP_STRIP_HEIGHT) {
+      const y1 = Math.min(y0 + SCALE_UP_STRIP_HEIGHT, outputHeight)
+      strip = scale_up_rows(input, padding, scale, 'bicubic', 0, 0, monotone, 'replicate', 0, y0, y1)
+      result.set(strip.view(), y0 * outputWidth)
+      strip.free()
+      strip = undefined
+    }
 
     return result
   } catch (error) {
     console.error(error)
   } finally {
-    if (inputPtr !== undefined) {
-      free_memory(inputPtr, data.length)
-    }
-    if (outputPtr !== undefined) {
-      free_memory(outputPtr, outputLength)
-    }
-    instance = null
+    input?.free()
+    strip?.free()
   }
 }
 
-export const scaleDownWorldMap = (data: Float32Array) => {
-  const result = new Float32Array(16777216)   // 4096^2
-  const width = 16384
-
-  for (let y = 0; y < 4096; y++) {
-    const baseIndex = y * 4 * width
-    const resultIndex = y * 4096
-    for (let x = 0; x < 4096; x++) {
-      const index = baseIndex + x * 4
-      result[resultIndex + x] = (
-        data[index + width + 1]
-        + data[index + width + 2]
-        + data[index + 2 * width + 1]
-        + data[index + 2 * width + 2]
-      ) * 0.25
-    }
-  }
-
-  return result
-}
-
-export const blendMapsWithFeathering = async (worldMap: Float32Array, heightmap: Float32Array, featherSize: number) => {
-  const padding = 100
-  const worldMapSize = 16584
-  const heightmapSize = 4296
-
-  const result = worldMap
-
-  const startX = Math.floor((worldMapSize - heightmapSize) / 2)
-  const startY = Math.floor((worldMapSize - heightmapSize) / 2)
+export const scaleDownWorldMap = async (data: Float32Array, width = 16384, height = 16384, factor = 4, filter: 'box' | 'area' | 'lanczos2' | 'lanczos3' = 'area', border: BorderMode = 'replicate', borderValue = 0) => {
+  let input: HeightBuffer | undefined
+  let strip: HeightBuffer | undefined
+  await init()
 
-  // core position
-  const coreStart = padding
-  const coreEnd = heightmapSize - padding
-
-  // 1. core
-  for (let yB = coreStart; yB < coreEnd; yB++) {
-    const yA = startY + yB
-    for (let xB = coreStart; xB < coreEnd; xB++) {
-      const xA = startX + xB
-      result[yA * worldMapSize + xA] = heightmap[yB * heightmapSize + xB]
-    }
-  }
-
-  // 2. top
-  for (let yB = coreStart - featherSize; yB < coreStart; yB++) {
-    const yA = startY + yB
-    for (let xB = coreStart; xB < coreEnd; xB++) {
-      const xA = startX + xB
-      const weight = (yB - (coreStart - featherSize)) / featherSize
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
+  try {
+    const [outputWidth, outputHeight] = scale_down_size(width, height, factor)
+    const result = new Float32Array(outputWidth * outputHeight)
+
+    for (let y0 = 0; y0 < outputHeight; y0 += SCALE_DOWN_STRIP_HEIGHT) {
+      const y1 = Math.min(y0 + SCALE_DOWN_STRIP_HEIGHT, outputHeight)
+      const [r0, r1] = scale_down_source_rows(width, height, factor, filter, border, borderValue, y0, y1)
+      input = HeightBuffer.from_data(width, r1 - r0, data.subarray(r0 * width, r1 * width))
+      strip = scale_down_rows(input, height, r0, factor, filter, border, borderValue, y0, y1)
+      result.set(strip.view(), y0 * outputWidth)
+      input.free()
+      strip.free()
+      input = undefined
+      strip = undefined
     }
-  }
 
-  // 3. bottom
-  for (let yB = coreEnd; yB < coreEnd + featherSize; yB++) {
-    const yA = startY + yB
-    for (let xB = coreStart; xB < coreEnd; xB++) {
-      const xA = startX + xB
-      const weight = (coreEnd + featherSize - yB) / featherSize
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
-    }
+    return result
+  } finally {
+    input?.free()
+    strip?.free()
   }
+}
 
-  // 4. left
-  for (let yB = coreStart; yB < coreEnd; yB++) {
-    const yA = startY + yB
-    for (let xB = coreStart - featherSize; xB < coreStart; xB++) {
-      const xA = startX + xB
-      const weight = (xB - (coreStart - featherSize)) / featherSize
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
-    }
-  }
+export const blendMapsWithFeathering = async (
+  worldMap: Float32Array,
+  heightmap: Float32Array,
+  featherSize: number,
+  worldMapSize = 16584,
+  heightmapSize = 4296,
+  padding = 100,
+  curve: 'linear' | 'smoothstep' | 'cosine' = 'linear',
+  roundedCorners = false,
+) => {
+  let base: HeightBuffer | undefined
+  let overlay: HeightBuffer | undefined
+  await init()
 
-  // 5. right
-  for (let yB = coreStart; yB < coreEnd; yB++) {
-    const yA = startY + yB
-    for (let xB = coreEnd; xB < coreEnd + featherSize; xB++) {
-      const xA = startX + xB
-      const weight = (coreEnd + featherSize - xB) / featherSize
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
+  try {
+    // The blend only changes the part of the world map under the heightmap,
+    // so only that window goes through wasm memory and is written back in place.
+    const start = Math.floor((worldMapSize - heightmapSize) / 2)
+    const x0 = Math.max(start, 0)
+    const x1 = Math.min(start + heightmapSize, worldMapSize)
+    const windowSize = x1 - x0
+    const window = new Float32Array(windowSize * windowSize)
+
+    for (let y = 0; y < windowSize; y++) {
+      const srcStart = (x0 + y) * worldMapSize + x0
+      window.set(worldMap.subarray(srcStart, srcStart + windowSize), y * windowSize)
     }
-  }
 
-  // 6. corner
-  // top-left
-  for (let yB = coreStart - featherSize; yB < coreStart; yB++) {
-    const yA = startY + yB
-    for (let xB = coreStart - featherSize; xB < coreStart; xB++) {
-      const xA = startX + xB
-      const weightY = (yB - (coreStart - featherSize)) / featherSize
-      const weightX = (xB - (coreStart - featherSize)) / featherSize
-      const weight = Math.min(weightX, weightY)
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
-    }
-  }
+    base = HeightBuffer.from_data(windowSize, windowSize, window)
+    overlay = HeightBuffer.from_data(heightmapSize, heightmapSize, heightmap)
+    blend_maps(base, overlay, start - x0, start - x0, padding, featherSize, curve, roundedCorners)
 
-  // top-right
-  for (let yB = coreStart - featherSize; yB < coreStart; yB++) {
-    const yA = startY + yB
-    for (let xB = coreEnd; xB < coreEnd + featherSize; xB++) {
-      const xA = startX + xB
-      const weightY = (yB - (coreStart - featherSize)) / featherSize
-      const weightX = (coreEnd + featherSize - xB) / featherSize
-      const weight = Math.min(weightX, weightY)
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
+    const blended = base.view()
+    for (let y = 0; y < windowSize; y++) {
+      worldMap.set(blended.subarray(y * windowSize, (y + 1) * windowSize), (x0 + y) * worldMapSize + x0)
     }
-  }
 
-  // bottom-left
-  for (let yB = coreEnd; yB < coreEnd + featherSize; yB++) {
-    const yA = startY + yB
-    for (let xB = coreStart - featherSize; xB < coreStart; xB++) {
-      const xA = startX + xB
-      const weightY = (coreEnd + featherSize - yB) / featherSize
-      const weightX = (xB - (coreStart - featherSize)) / featherSize
-      const weight = Math.min(weightX, weightY)
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
-    }
+    return worldMap
+  } finally {
+    base?.free()
+    overlay?.free()
   }
+}
 
-  // bottom-right
-  for (let yB = coreEnd; yB < coreEnd + featherSize; yB++) {
-    const yA = startY + yB
-    for (let xB = coreEnd; xB < coreEnd + featherSize; xB++) {
-      const xA = startX + xB
-      const weightY = (coreEnd + featherSize - yB) / featherSize
-      const weightX = (coreEnd + featherSize - xB) / featherSize
-      const weight = Math.min(weightX, weightY)
-      const indexB = yB * heightmapSize + xB
-      const indexA = yA * worldMapSize + xA
-      result[indexA] = heightmap[indexB] * weight + result[indexA] * (1 - weight)
-    }
-  }
+// Distance in pixels between the tiles of splitTile
+const splitTileStep = (size: number, divisions: number, padding: number) => Math.floor((size - padding * 2) / divisions)
 
-  return result
+// [x, y] of the top-left pixel of tile `index` of splitTile in the whole map
+export const splitTileOffset = (size: number, divisions: number, padding: number, index: number) => {
+  const dividedSize = splitTileStep(size, divisions, padding)
+  return [(index % divisions) * dividedSize, Math.floor(index / divisions) * dividedSize]
 }
 
 export const splitTile = async (data: Float32Array, divisions: number, padding: number) => {
   const size = Math.sqrt(data.length)
   const coreSize = size - padding * 2
-  const dividedSize = Math.floor(coreSize / divisions)
+  const dividedSize = splitTileStep(size, divisions, padding)
   const correction = coreSize % dividedSize
   let tileSize = dividedSize + padding * 2 + correction
 
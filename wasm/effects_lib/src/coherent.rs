@@ -0,0 +1,161 @@
+use crate::hash::{pixel_hash, unit_float};
+
+#[derive(Clone, Copy)]
+pub enum NoiseType {
+    // fBm of gradient (Perlin) noise
+    Perlin,
+    // fBm of simplex noise
+    Simplex,
+    // Ridged multifractal of gradient noise. Sharp crests, and less detail in the valleys.
+    Ridged,
+    // fBm of the absolute value of gradient noise. Rounded hills.
+    Billow,
+    // fBm of the distance to the nearest feature point (cellular noise)
+    Worley,
+}
+
+impl NoiseType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "perlin" => Some(Self::Perlin),
+            "simplex" => Some(Self::Simplex),
+            "ridged" => Some(Self::Ridged),
+            "billow" => Some(Self::Billow),
+            "worley" => Some(Self::Worley),
+            _ => None,
+        }
+    }
+}
+
+pub struct Fractal {
+    pub noise_type: NoiseType,
+    pub octaves: u32,
+    // Frequency factor between octaves
+    pub lacunarity: f64,
+    // Amplitude factor between octaves
+    pub gain: f64,
+    pub seed: u32,
+}
+
+impl Fractal {
+    // Value in [0, 1] at (x, y), in units of the base wavelength
+    pub fn sample(&self, x: f64, y: f64) -> f32 {
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut amplitude_sum = 0.0;
+        let mut sum = 0.0;
+        // Ridged: the previous octave scales the next one, so the detail follows the crests.
+        let mut weight = 1.0;
+
+        for octave in 0..self.octaves {
+            let seed = self.seed.wrapping_add(octave.wrapping_mul(0x9E37_79B9));
+            let (px, py) = (x * frequency, y * frequency);
+
+            let value = match self.noise_type {
+                NoiseType::Perlin => gradient_noise(seed, px, py),
+                NoiseType::Simplex => simplex_noise(seed, px, py),
+                NoiseType::Ridged => {
+                    let signal = 1.0 - gradient_noise(seed, px, py).abs();
+                    let signal = signal * signal * weight;
+                    weight = signal.clamp(0.0, 1.0);
+                    signal
+                }
+                NoiseType::Billow => gradient_noise(seed, px, py).abs(),
+                NoiseType::Worley => 2.0 * worley_noise(seed, px, py) - 1.0,
+            };
+
+            sum += value * amplitude;
+            amplitude_sum += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        let value = if amplitude_sum > 0.0 { sum / amplitude_sum } else { 0.0 };
+        match self.noise_type {
+            NoiseType::Ridged | NoiseType::Billow => value.clamp(0.0, 1.0) as f32,
+            _ => (0.5 * value + 0.5).clamp(0.0, 1.0) as f32,
+        }
+    }
+}
+
+// Unit gradient of the lattice point (x, y), one of 8 directions
+fn gradient(seed: u32, x: i64, y: i64) -> (f64, f64) {
+    const D: f64 = std::f64::consts::FRAC_1_SQRT_2;
+    const GRADIENTS: [(f64, f64); 8] = [(1.0, 0.0), (D, D), (0.0, 1.0), (-D, D), (-1.0, 0.0), (-D, -D), (0.0, -1.0), (D, -D)];
+    GRADIENTS[(pixel_hash(seed as u64, x, y) >> 61) as usize]
+}
+
+fn quintic(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+// Perlin gradient noise in [-1, 1]
+fn gradient_noise(seed: u32, x: f64, y: f64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i64, y0 as i64);
+
+    let corner = |dx: i64, dy: i64| {
+        let (gx, gy) = gradient(seed, ix + dx, iy + dy);
+        gx * (fx - dx as f64) + gy * (fy - dy as f64)
+    };
+
+    let (u, v) = (quintic(fx), quintic(fy));
+    let bottom = corner(0, 0) + u * (corner(1, 0) - corner(0, 0));
+    let top = corner(0, 1) + u * (corner(1, 1) - corner(0, 1));
+    // Unit gradients reach at most sqrt(1/2) in 2D
+    ((bottom + v * (top - bottom)) * std::f64::consts::SQRT_2).clamp(-1.0, 1.0)
+}
+
+// Simplex noise in [-1, 1]
+fn simplex_noise(seed: u32, x: f64, y: f64) -> f64 {
+    let f2 = 0.5 * (3f64.sqrt() - 1.0);
+    let g2 = (3.0 - 3f64.sqrt()) / 6.0;
+
+    // Skew to the simplex grid and find the triangle that contains the point
+    let s = (x + y) * f2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * g2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let (i, j) = (i as i64, j as i64);
+
+    let corners = [
+        (0, 0, x0, y0),
+        (i1, j1, x0 - i1 as f64 + g2, y0 - j1 as f64 + g2),
+        (1, 1, x0 - 1.0 + 2.0 * g2, y0 - 1.0 + 2.0 * g2),
+    ];
+
+    let sum: f64 = corners.iter()
+        .map(|&(di, dj, dx, dy)| {
+            let t = 0.5 - dx * dx - dy * dy;
+            if t <= 0.0 {
+                return 0.0;
+            }
+            let (gx, gy) = gradient(seed, i + di, j + dj);
+            t * t * t * t * (gx * dx + gy * dy)
+        })
+        .sum();
+
+    // Scales the largest possible sum with unit gradients to about 1
+    (sum * 99.2).clamp(-1.0, 1.0)
+}
+
+// Distance to the nearest feature point in [0, 1], with one jittered point per lattice cell
+fn worley_noise(seed: u32, x: f64, y: f64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let mut nearest = f64::INFINITY;
+
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let hash = pixel_hash(seed as u64, ix + dx, iy + dy);
+            let px = x0 + dx as f64 + unit_float(hash) as f64;
+            let py = y0 + dy as f64 + unit_float(hash << 24) as f64;
+            let d = (px - x).powi(2) + (py - y).powi(2);
+            nearest = nearest.min(d);
+        }
+    }
+
+    nearest.sqrt().min(1.0)
+}
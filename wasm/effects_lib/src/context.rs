@@ -0,0 +1,101 @@
+use std::collections::{HashMap, VecDeque};
+use wasm_bindgen::prelude::*;
+use rustfft::{num_complex::Complex, FftPlanner};
+use crate::border::BorderMode;
+use crate::fft::{fft_blur, gaussian_spectrum, line_blur, Fft2d};
+use crate::gaussian::gaussian_reach;
+use crate::spatial::separable_blur;
+
+// Largest kernel reach blurred with the separable convolution (radius 25 reaches 24 pixels).
+// Timed single-threaded on 2048x2048 and 4296x4296 maps, the separable blur is faster up to a reach of 24,
+// and the FFT, including the transform of the kernel, is faster from a reach of 32.
+const SEPARABLE_MAX_REACH: usize = 24;
+
+// Memory the cached kernel spectra may take. Each spectrum is as large as the padded map
+// (8 bytes per pixel), so older ones are dropped first. The newest spectrum is always kept.
+const MAX_KERNEL_BYTES: usize = 256 << 20;
+
+// Padded width and height and the bits of the radius
+type KernelKey = (usize, usize, u32);
+
+// FFT plans and Gaussian kernel spectra kept between calls.
+// Processing many tiles of the same size with the same radius only plans and transforms the kernel once.
+#[wasm_bindgen]
+pub struct EffectsContext {
+    planner: FftPlanner<f32>,
+    // Keyed by the padded width and height
+    plans: HashMap<(usize, usize), Fft2d>,
+    // Least recently used first
+    kernels: VecDeque<(KernelKey, Vec<Complex<f32>>)>,
+}
+
+#[wasm_bindgen]
+impl EffectsContext {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> EffectsContext {
+        Self {
+            planner: FftPlanner::new(),
+            plans: HashMap::new(),
+            kernels: VecDeque::new(),
+        }
+    }
+
+    // Releases the cached plans and spectra
+    pub fn clear(&mut self) {
+        self.plans.clear();
+        self.kernels.clear();
+    }
+}
+
+impl Default for EffectsContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EffectsContext {
+    // Gaussian blur of a width x height raster. Small radii use the separable convolution,
+    // larger ones the FFT of the padded map (see fft::fft_blur) or of every line (see fft::line_blur).
+    // All give the same result.
+    pub fn blur(&mut self, data: &[f32], width: usize, height: usize, radius: f32, border: &BorderMode) -> Vec<f32> {
+        let padding = gaussian_reach(radius);
+        if padding <= SEPARABLE_MAX_REACH {
+            return separable_blur(data, width, height, radius, border);
+        }
+        let size = (width + 2 * padding, height + 2 * padding);
+        // Past twice the map, the padded map is not worth it. Blur line by line instead,
+        // which also keeps large radii from running out of memory.
+        if size.0 as f64 * size.1 as f64 > 2.0 * (width * height) as f64 {
+            return line_blur(&mut self.planner, data, width, height, radius, border);
+        }
+
+        let planner = &mut self.planner;
+        let fft = self.plans.entry(size)
+            .or_insert_with(|| Fft2d::new(planner, size.0, size.1));
+
+        let key = (size.0, size.1, radius.to_bits());
+        let entry = match self.kernels.iter().position(|(k, _)| *k == key) {
+            Some(i) => self.kernels.remove(i).unwrap(),
+            None => (key, gaussian_spectrum(fft, radius)),
+        };
+        self.kernels.push_back(entry);
+        self.evict();
+
+        let fft = &self.plans[&size];
+        let (_, kernel) = self.kernels.back().unwrap();
+        fft_blur(fft, kernel, data, width, height, padding, border)
+    }
+
+    // Drops the least recently used spectra over MAX_KERNEL_BYTES, and the plans no spectrum uses
+    fn evict(&mut self) {
+        let mut total: usize = self.kernels.iter().map(|(_, kernel)| std::mem::size_of_val(kernel.as_slice())).sum();
+        while total > MAX_KERNEL_BYTES && self.kernels.len() > 1 {
+            if let Some((_, kernel)) = self.kernels.pop_front() {
+                total -= std::mem::size_of_val(kernel.as_slice());
+            }
+        }
+
+        let kernels = &self.kernels;
+        self.plans.retain(|&(w, h), _| kernels.iter().any(|&((kw, kh, _), _)| (kw, kh) == (w, h)));
+    }
+}
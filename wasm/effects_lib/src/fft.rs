@@ -0,0 +1,201 @@
+use std::sync::Arc;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use rayon::prelude::*;
+use crate::border::{add_padding, BorderMode};
+use crate::gaussian::{gaussian_reach, gaussian_sigma};
+use crate::spatial::gaussian_weights;
+
+// 2D FFT of a width x height raster, computed as FFTs of the rows and then of the columns.
+// The spectrum is stored transposed (width rows of height values), which saves transposing it back.
+pub struct Fft2d {
+    width: usize,
+    height: usize,
+    row_forward: Arc<dyn Fft<f32>>,
+    row_inverse: Arc<dyn Fft<f32>>,
+    column_forward: Arc<dyn Fft<f32>>,
+    column_inverse: Arc<dyn Fft<f32>>,
+}
+
+impl Fft2d {
+    pub fn new(planner: &mut FftPlanner<f32>, width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            row_forward: planner.plan_fft_forward(width),
+            row_inverse: planner.plan_fft_inverse(width),
+            column_forward: planner.plan_fft_forward(height),
+            column_inverse: planner.plan_fft_inverse(height),
+        }
+    }
+
+    pub fn forward(&self, mut data: Vec<Complex<f32>>) -> Vec<Complex<f32>> {
+        process_lines(&mut data, self.width, &self.row_forward);
+        let mut spectrum = transpose(&data, self.width, self.height);
+        process_lines(&mut spectrum, self.height, &self.column_forward);
+        spectrum
+    }
+
+    // Inverse of forward, including the 1 / (width * height) scale
+    pub fn inverse(&self, mut spectrum: Vec<Complex<f32>>) -> Vec<Complex<f32>> {
+        process_lines(&mut spectrum, self.height, &self.column_inverse);
+        let mut data = transpose(&spectrum, self.height, self.width);
+        process_lines(&mut data, self.width, &self.row_inverse);
+
+        let scale = 1.0 / (self.width * self.height) as f32;
+        data.par_iter_mut().for_each(|d| *d *= scale);
+        data
+    }
+}
+
+// Transforms every line of `len` values in place
+fn process_lines(data: &mut [Complex<f32>], len: usize, fft: &Arc<dyn Fft<f32>>) {
+    let scratch = || vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+    data.par_chunks_exact_mut(len)
+        .for_each_init(scratch, |scratch, line| fft.process_with_scratch(line, scratch));
+}
+
+fn transpose<T: Copy + Default + Send + Sync>(data: &[T], width: usize, height: usize) -> Vec<T> {
+    let mut result = vec![T::default(); data.len()];
+    result.par_chunks_exact_mut(height)
+        .enumerate()
+        .for_each(|(x, column)| {
+            for (y, value) in column.iter_mut().enumerate() {
+                *value = data[y * width + x];
+            }
+        });
+    result
+}
+
+// Normalized Gaussian cut off at gaussian_reach, centered on (0, 0) of a width x height raster.
+// Negative offsets wrap around to the far edge, so the convolution needs no shift afterwards.
+fn gaussian_kernel(width: usize, height: usize, radius: f32) -> Vec<Complex<f32>> {
+    let sigma = gaussian_sigma(radius);
+    let reach = gaussian_reach(radius) as isize;
+    let weight = |i: usize, len: usize| {
+        let d = if i <= len / 2 { i as isize } else { i as isize - len as isize };
+        if d.abs() > reach { 0.0 } else { (-((d * d) as f32) / (2.0 * sigma * sigma)).exp() }
+    };
+
+    let wx: Vec<f32> = (0..width).map(|x| weight(x, width)).collect();
+    let wy: Vec<f32> = (0..height).map(|y| weight(y, height)).collect();
+    let sum = wx.iter().sum::<f32>() * wy.iter().sum::<f32>();
+
+    let mut kernel = vec![Complex::new(0.0, 0.0); width * height];
+    kernel.par_chunks_exact_mut(width)
+        .zip(wy.par_iter())
+        .for_each(|(row, &y)| {
+            for (k, &x) in row.iter_mut().zip(&wx) {
+                *k = Complex::new(x * y / sum, 0.0);
+            }
+        });
+    kernel
+}
+
+// Spectrum of the Gaussian of `radius` for convolutions with `fft`
+pub fn gaussian_spectrum(fft: &Fft2d, radius: f32) -> Vec<Complex<f32>> {
+    fft.forward(gaussian_kernel(fft.width, fft.height, radius))
+}
+
+// Gaussian blur with a 2D FFT. The input is extended by `border` by gaussian_reach pixels,
+// so the circular convolution never wraps around from one edge to the other.
+// `fft` and `kernel` must be planned for the padded size.
+pub fn fft_blur(fft: &Fft2d, kernel: &[Complex<f32>], data: &[f32], width: usize, height: usize, padding: usize, border: &BorderMode) -> Vec<f32> {
+    let padded_width = width + 2 * padding;
+
+    let padded: Vec<Complex<f32>> = add_padding(data, width, height, padding, border)
+        .into_par_iter()
+        .map(|v| Complex::new(v, 0.0))
+        .collect();
+    let mut spectrum = fft.forward(padded);
+
+    spectrum.par_iter_mut()
+        .zip(kernel.par_iter())
+        .for_each(|(s, &k)| *s *= k);
+
+    let blurred = fft.inverse(spectrum);
+
+    let mut result = vec![0.0; width * height];
+    result.par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let offset = (y + padding) * padded_width + padding;
+            for (value, b) in row.iter_mut().zip(&blurred[offset..offset + width]) {
+                *value = b.re;
+            }
+        });
+    result
+}
+
+// Gaussian blur of every row and then of every column, each line with a 1D FFT.
+// Gives the same result as fft_blur, but pads every line by at most its own length instead of
+// padding the whole map by the reach, so the memory stays close to the size of the map for any radius.
+pub fn line_blur(planner: &mut FftPlanner<f32>, data: &[f32], width: usize, height: usize, radius: f32, border: &BorderMode) -> Vec<f32> {
+    let weights = gaussian_weights(radius);
+    let rows = blur_lines(planner, data, width, &weights, border);
+    let columns = blur_lines(planner, &transpose(&rows, width, height), height, &weights, border);
+    transpose(&columns, height, width)
+}
+
+// Convolves every line of `len` values with `weights` (2 * reach + 1 taps), extended by `border`.
+// Wrap and reflect repeat every len and 2 * len values, so the kernel folds onto one period.
+// Replicate and constant read the edge value beyond the line, so the line is zero padded up to the
+// taps that can reach it, and the weight of the taps past the edge is added times the edge value.
+fn blur_lines(planner: &mut FftPlanner<f32>, data: &[f32], len: usize, weights: &[f32], border: &BorderMode) -> Vec<f32> {
+    let reach = weights.len() / 2;
+    let (fft_len, periodic) = match border {
+        BorderMode::Wrap => (len, true),
+        BorderMode::Reflect => (2 * len, true),
+        BorderMode::Replicate | BorderMode::Constant(_) => (len + reach.min(len - 1), false),
+    };
+
+    let mut kernel = vec![Complex::new(0.0, 0.0); fft_len];
+    for (i, &w) in weights.iter().enumerate() {
+        let d = i as isize - reach as isize;
+        if periodic || d.unsigned_abs() < len {
+            kernel[d.rem_euclid(fft_len as isize) as usize].re += w;
+        }
+    }
+
+    // tails[m]: weight of the taps at m or more pixels on one side
+    let mut tails = vec![0.0; len + 1];
+    let mut tail = 0.0;
+    for m in (1..=reach).rev() {
+        tail += weights[reach + m];
+        if m <= len {
+            tails[m] = tail;
+        }
+    }
+
+    let forward = planner.plan_fft_forward(fft_len);
+    let inverse = planner.plan_fft_inverse(fft_len);
+    forward.process(&mut kernel);
+    let scale = 1.0 / fft_len as f32;
+    kernel.iter_mut().for_each(|k| *k *= scale);
+
+    let scratch_len = forward.get_inplace_scratch_len().max(inverse.get_inplace_scratch_len());
+    let scratch = || (vec![Complex::new(0.0, 0.0); fft_len], vec![Complex::new(0.0, 0.0); scratch_len]);
+
+    let mut result = vec![0.0; data.len()];
+    result.par_chunks_exact_mut(len)
+        .zip(data.par_chunks_exact(len))
+        .for_each_init(scratch, |(buffer, scratch), (output, line)| {
+            for (i, value) in buffer.iter_mut().enumerate() {
+                let v = if i < len || periodic { border.sample(line, len, 1, i as isize, 0) } else { 0.0 };
+                *value = Complex::new(v, 0.0);
+            }
+
+            forward.process_with_scratch(buffer, scratch);
+            buffer.iter_mut().zip(&kernel).for_each(|(b, &k)| *b *= k);
+            inverse.process_with_scratch(buffer, scratch);
+
+            let (left, right) = match border {
+                BorderMode::Replicate => (line[0], line[len - 1]),
+                BorderMode::Constant(value) => (*value, *value),
+                BorderMode::Wrap | BorderMode::Reflect => (0.0, 0.0),
+            };
+            for (x, value) in output.iter_mut().enumerate() {
+                *value = buffer[x].re + left * tails[x + 1] + right * tails[len - x];
+            }
+        });
+    result
+}
@@ -1,70 +1,165 @@
+#[path = "../../shared/border.rs"]
+mod border;
+mod buffer;
+mod coherent;
+mod context;
+mod fft;
+#[path = "../../shared/gaussian.rs"]
+mod gaussian;
+#[path = "../../shared/hash.rs"]
+mod hash;
+mod mask;
+mod ramp;
+#[path = "../../shared/raster.rs"]
+mod raster;
+mod relief;
+mod spatial;
+mod terrain;
+
 use wasm_bindgen::prelude::*;
-use std::sync::{Arc, Mutex};
-use rustfft::{num_complex::Complex, FftPlanner};
-use rand::{rngs::SmallRng, Rng, SeedableRng};
+use border::BorderMode;
+pub use buffer::HeightBuffer;
+use coherent::{Fractal, NoiseType};
+pub use context::EffectsContext;
+use gaussian::gaussian_reach;
+use hash::{pixel_hash, unit_float};
+use mask::{effect_alpha, Mask};
+pub use mask::EffectMask;
+use ramp::{Band, FadeShape};
+use relief::{ShadeMode, Tint};
+use terrain::{CurvatureType, SlopeMethod, SlopeUnit};
 use rayon::prelude::*;
 
-#[wasm_bindgen]
-pub fn allocate_memory(length: usize) -> *mut f32 {
-    let mut buffer = Vec::with_capacity(length);
-    unsafe { buffer.set_len(length) }
-    let ptr = buffer.as_mut_ptr();
-    std::mem::forget(buffer);
-    ptr
+fn validate_size(name: &str, input: &HeightBuffer, other: &HeightBuffer) -> Result<(), JsError> {
+    if input.width() != other.width() || input.height() != other.height() {
+        return Err(JsError::new(&format!(
+            "{}: Buffer size mismatch, {}x{} and {}x{}", name, input.width(), input.height(), other.width(), other.height()
+        )));
+    }
+    Ok(())
 }
 
-#[wasm_bindgen]
-pub fn free_memory(ptr: *mut f32, size: usize) {
-    unsafe {
-        let _ = Vec::from_raw_parts(ptr, size, size);
+fn validate_number(name: &str, param: &str, value: f32) -> Result<(), JsError> {
+    if !value.is_finite() {
+        return Err(JsError::new(&format!("{}: {} must be a finite number", name, param)));
     }
+    Ok(())
 }
 
-#[wasm_bindgen]
-pub fn gaussian_blur(input_ptr: *mut f32, output_ptr: *mut f32, length: usize, radius: f32, blend_factor: f32, threshold: f32, fade: f32) {
-    let size = (length as f32).sqrt() as usize;
+// border: "replicate" | "reflect" | "wrap" | "constant". `border_value` is only used by "constant".
+fn border_mode(name: &str, border: &str, border_value: f32) -> Result<BorderMode, JsError> {
+    BorderMode::from_name(border, border_value)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid border mode or value", name)))
+}
 
-    let input_slice = unsafe { std::slice::from_raw_parts(input_ptr, length) };
-    let output_slice = unsafe { std::slice::from_raw_parts_mut(output_ptr, length) };
+// shape: "linear" | "smoothstep" | "smootherstep" | "cosine"
+fn fade_shape(name: &str, shape: &str) -> Result<FadeShape, JsError> {
+    FadeShape::from_name(shape)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid fade shape", name)))
+}
 
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(length);
-    let ifft = planner.plan_fft_inverse(length);
+// [low, high] with fades of `fade_low` below and `fade_high` above it (see ramp::Band).
+// An infinite low or high leaves that side of the band open.
+fn band(name: &str, low: f32, high: f32, fade_low: f32, fade_high: f32, shape: FadeShape) -> Result<Band, JsError> {
+    if !(low <= high && low < f32::INFINITY && high > f32::NEG_INFINITY) {
+        return Err(JsError::new(&format!("{}: Low must not exceed high and both must be numbers", name)));
+    }
+    if !(fade_low.is_finite() && fade_low >= 0.0 && fade_high.is_finite() && fade_high >= 0.0) {
+        return Err(JsError::new(&format!("{}: Fade must be a non-negative number", name)));
+    }
+    Ok(Band::new(low, high, fade_low, fade_high, shape))
+}
 
-    let mut input_complex: Vec<Complex<f32>> = (0..length)
-        .into_par_iter()
-        .map(|i| Complex::new(input_slice[i], 0.0))
-        .collect();
-    fft.process(&mut input_complex);
+// Largest Gaussian reach in pixels. The blurs handle any reach without padding the whole map,
+// but the 1D kernel still has 2 * reach + 1 taps.
+const MAX_BLUR_REACH: usize = 1 << 20;
 
-    let mut kernel = generate_gaussian_kernel(size, radius);
-    fft.process(&mut kernel);
+fn validate_radius(name: &str, radius: f32) -> Result<(), JsError> {
+    if !(radius.is_finite() && radius > 0.0) {
+        return Err(JsError::new(&format!("{}: Radius must be a positive number", name)));
+    }
+    if gaussian_reach(radius) > MAX_BLUR_REACH {
+        return Err(JsError::new(&format!("{}: Radius is too large", name)));
+    }
+    Ok(())
+}
 
-    input_complex.par_iter_mut()
-        .zip(kernel.par_iter())
-        .for_each(|(i, &k)| {
-            *i *= k;
-        });
+// Radius of the blur of the noise TRI mask, so that the slope of the noise boundary does not exceed 45 degrees
+fn tri_blur_radius(amount: f32, pixel_distance: f32) -> f32 {
+    (amount / pixel_distance).max(1.0)
+}
 
-    ifft.process(&mut input_complex);
-    fft_shift_2d(&mut input_complex, size);
+fn validate_noise(name: &str, input: &HeightBuffer, output: &HeightBuffer, amount: f32, tri_threshold: f32, pixel_distance: f32) -> Result<(), JsError> {
+    validate_size(name, input, output)?;
+    validate_number(name, "Amount", amount)?;
+    if !(tri_threshold.is_finite() && tri_threshold >= 0.0) {
+        return Err(JsError::new(&format!("{}: TRI threshold must be a non-negative number", name)));
+    }
+    if !(pixel_distance.is_finite() && pixel_distance > 0.0) {
+        return Err(JsError::new(&format!("{}: Pixel distance must be a positive number", name)));
+    }
+    if tri_threshold > 0.0 && gaussian_reach(tri_blur_radius(amount, pixel_distance)) > MAX_BLUR_REACH {
+        return Err(JsError::new(&format!("{}: Amount is too large for the pixel distance", name)));
+    }
+    Ok(())
+}
 
-    let len = length as f32; 
+// View of `mask` for an effect on `input`, or None for EffectMask.none()
+fn effect_mask<'a>(name: &str, input: &HeightBuffer, mask: &'a EffectMask) -> Result<Option<Mask<'a>>, JsError> {
+    let Some((alpha, mode)) = mask.alpha() else {
+        return Ok(None);
+    };
+    validate_size(name, input, alpha)?;
+    Ok(Some(Mask { data: alpha.data(), mode }))
+}
 
-    output_slice.par_iter_mut()
-        .enumerate()
-        .for_each(|(i, output)| {
-            let blurred = input_complex[i].re / len;
-            let elevation_alpha = get_ul_alpha(input_slice[i], threshold, fade);
-            *output = (1.0 - blend_factor * elevation_alpha) * input_slice[i] + blend_factor * elevation_alpha * blurred;
-        });
+// The blur applies to elevations in the band from `low` to `high`, fading out over `fade_low` metres
+// below it and `fade_high` metres above it along `shape` (see band and fade_shape).
+// `border` and `border_value` set how the blur reads pixels outside the input (see border_mode).
+// `mask` sets the alpha of every pixel (see mask::EffectMask).
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn gaussian_blur(
+    input: &HeightBuffer,
+    output: &mut HeightBuffer,
+    radius: f32,
+    blend_factor: f32,
+    low: f32,
+    high: f32,
+    fade_low: f32,
+    fade_high: f32,
+    shape: &str,
+    border: &str,
+    border_value: f32,
+    mask: &EffectMask,
+) -> Result<(), JsError> {
+    EffectsContext::new().gaussian_blur(input, output, radius, blend_factor, low, high, fade_low, fade_high, shape, border, border_value, mask)
 }
 
+// The elevation band and mask are the same as in gaussian_blur
 #[wasm_bindgen]
-pub fn unsharp_mask(input_ptr: *mut f32, blurred_ptr: *mut f32, output_ptr: *mut f32, length: usize, amount: f32, threshold: f32, fade: f32) {
-    let input_slice = unsafe { std::slice::from_raw_parts(input_ptr, length) };
-    let blurred_slice = unsafe { std::slice::from_raw_parts(blurred_ptr, length) };
-    let output_slice = unsafe { std::slice::from_raw_parts_mut(output_ptr, length) };
+#[allow(clippy::too_many_arguments)]
+pub fn unsharp_mask(
+    input: &HeightBuffer,
+    blurred: &HeightBuffer,
+    output: &mut HeightBuffer,
+    amount: f32,
+    low: f32,
+    high: f32,
+    fade_low: f32,
+    fade_high: f32,
+    shape: &str,
+    mask: &EffectMask,
+) -> Result<(), JsError> {
+    validate_size("unsharp_mask", input, blurred)?;
+    validate_size("unsharp_mask", input, output)?;
+    validate_number("unsharp_mask", "Amount", amount)?;
+    let band = band("unsharp_mask", low, high, fade_low, fade_high, fade_shape("unsharp_mask", shape)?)?;
+    let mask = effect_mask("unsharp_mask", input, mask)?;
+
+    let input_slice = input.data();
+    let blurred_slice = blurred.data();
+    let output_slice = output.data_mut();
 
     output_slice.par_iter_mut()
         .enumerate()
@@ -72,197 +167,421 @@ pub fn unsharp_mask(input_ptr: *mut f32, blurred_ptr: *mut f32, output_ptr: *mut
             let original = input_slice[i];
             let blurred = blurred_slice[i];
             let difference = original - blurred;
-            let elevation_alpha = get_ll_alpha(input_slice[i], threshold, fade);
+            let elevation_alpha = effect_alpha(mask.as_ref(), i, band.alpha(input_slice[i]));
             let sharpened = original + amount * difference;
             *output = (1.0 - elevation_alpha) * input_slice[i] + elevation_alpha * sharpened;
         });
+
+    Ok(())
 }
 
+// The noise of every pixel is a hash of `seed` and its position in the whole map,
+// where (offset_x, offset_y) is the position of the input. The same seed gives the same noise
+// regardless of the thread count or how the map is split into tiles.
+// The elevation band and mask are the same as in gaussian_blur.
+// `border` and `border_value` set how the blur of the TRI mask reads pixels outside the input (see border_mode).
 #[wasm_bindgen]
-pub fn noise(input_ptr: *mut f32, output_ptr: *mut f32, length: usize, amount: f32, tri_threshold: f32, pixel_distance: f32, threshold: f32, fade: f32) {
-    let size = (length as f32).sqrt() as usize;
-    let input_slice = unsafe { std::slice::from_raw_parts(input_ptr, length) };
-    let output_slice = unsafe { std::slice::from_raw_parts_mut(output_ptr, length) };
-    let rng = Arc::new(Mutex::new(SmallRng::from_entropy()));
-
-    if tri_threshold == 0.0 {
-        output_slice.par_iter_mut()
-            .enumerate()
-            .for_each(|(i, output_value)| {
-                let elevation_alpha = get_ll_alpha(input_slice[i], threshold, fade);
-                let mut thread_rng = rng.lock().unwrap();
-                *output_value = thread_rng.gen_range(0.0, 1.0) * amount * elevation_alpha;
-            });
-    } else {
-        let tri = calculate_tri(&input_slice, size);
-
-        let mut mask: Vec<Complex<f32>> = tri
-            .par_iter()
-            .map(|&v| if v >= tri_threshold { Complex::new(1.0, 0.0) } else { Complex::new(0.0, 0.0) })
-            .collect();
-
-        // blur mask
-        let mut planner = FftPlanner::new();
-        let fft = planner.plan_fft_forward(length);
-        let ifft = planner.plan_fft_inverse(length);
-
-        fft.process(&mut mask);
-
-        // Blur so that the slope of the noise boundary does not exceed 45 degrees.
-        let mut kernel = generate_gaussian_kernel(size, (amount / pixel_distance).max(1.0));
-        fft.process(&mut kernel);
-
-        mask.par_iter_mut()
-            .zip(kernel.par_iter())
-            .for_each(|(m, &k)| {
-                *m *= k;
-            });
+#[allow(clippy::too_many_arguments)]
+pub fn noise(
+    input: &HeightBuffer,
+    output: &mut HeightBuffer,
+    amount: f32,
+    tri_threshold: f32,
+    pixel_distance: f32,
+    low: f32,
+    high: f32,
+    fade_low: f32,
+    fade_high: f32,
+    shape: &str,
+    seed: u32,
+    offset_x: i32,
+    offset_y: i32,
+    border: &str,
+    border_value: f32,
+    mask: &EffectMask,
+) -> Result<(), JsError> {
+    EffectsContext::new().noise(
+        input, output, amount, tri_threshold, pixel_distance, low, high, fade_low, fade_high, shape, seed, offset_x, offset_y, border, border_value, mask,
+    )
+}
 
-        ifft.process(&mut mask);
-        fft_shift_2d(&mut mask, size);
+// Coherent noise in [0, amount), masked like noise.
+// noise_type: "perlin" | "simplex" | "ridged" | "billow" | "worley"
+// `wavelength` is the size of the base features in metres, and `pixel_distance` the size of a pixel in metres.
+// Every octave multiplies the frequency by `lacunarity` and the amplitude by `gain`.
+// The noise is a function of the position in the whole map, so tiles with the right offsets are seamless.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn coherent_noise(
+    input: &HeightBuffer,
+    output: &mut HeightBuffer,
+    amount: f32,
+    tri_threshold: f32,
+    pixel_distance: f32,
+    low: f32,
+    high: f32,
+    fade_low: f32,
+    fade_high: f32,
+    shape: &str,
+    noise_type: &str,
+    wavelength: f32,
+    octaves: u32,
+    lacunarity: f32,
+    gain: f32,
+    seed: u32,
+    offset_x: i32,
+    offset_y: i32,
+    border: &str,
+    border_value: f32,
+    mask: &EffectMask,
+) -> Result<(), JsError> {
+    EffectsContext::new().coherent_noise(
+        input, output, amount, tri_threshold, pixel_distance, low, high, fade_low, fade_high, shape,
+        noise_type, wavelength, octaves, lacunarity, gain, seed, offset_x, offset_y, border, border_value, mask,
+    )
+}
 
-        let len = length as f32;
+#[wasm_bindgen]
+impl EffectsContext {
+    // Same as gaussian_blur, reusing the plans and kernel spectra of earlier calls
+    #[allow(clippy::too_many_arguments)]
+    pub fn gaussian_blur(
+        &mut self,
+        input: &HeightBuffer,
+        output: &mut HeightBuffer,
+        radius: f32,
+        blend_factor: f32,
+        low: f32,
+        high: f32,
+        fade_low: f32,
+        fade_high: f32,
+        shape: &str,
+        border: &str,
+        border_value: f32,
+        mask: &EffectMask,
+    ) -> Result<(), JsError> {
+        validate_size("gaussian_blur", input, output)?;
+        validate_radius("gaussian_blur", radius)?;
+        validate_number("gaussian_blur", "Blend factor", blend_factor)?;
+        let band = band("gaussian_blur", low, high, fade_low, fade_high, fade_shape("gaussian_blur", shape)?)?;
+        let border = border_mode("gaussian_blur", border, border_value)?;
+        let mask = effect_mask("gaussian_blur", input, mask)?;
+
+        let input_slice = input.data();
+        let blurred_slice = self.blur(input_slice, input.width(), input.height(), radius, &border);
+        let output_slice = output.data_mut();
 
         output_slice.par_iter_mut()
             .enumerate()
-            .for_each(|(i, output_value)| {
-                let elevation_alpha = get_ll_alpha(input_slice[i], threshold, fade);
-                let mut thread_rng = rng.lock().unwrap();
-                *output_value = thread_rng.gen_range(0.0, 1.0) * amount * (mask[i].re / len) * elevation_alpha;
+            .for_each(|(i, output)| {
+                let blurred = blurred_slice[i];
+                let elevation_alpha = effect_alpha(mask.as_ref(), i, band.alpha(input_slice[i]));
+                *output = (1.0 - blend_factor * elevation_alpha) * input_slice[i] + blend_factor * elevation_alpha * blurred;
             });
-    }
-}
-
-fn rotate_rows(data: &mut [Complex<f32>], size: usize, shift: isize) {
-    let row_count = data.len() / size;
-    let shift = ((shift % row_count as isize) + row_count as isize) as usize % row_count;
 
-    if shift == 0 {
-        return;
+        Ok(())
     }
 
-    data.rotate_left(shift * size);
-}
-
-fn fft_shift_2d(data: &mut [Complex<f32>], size: usize) {
-    let mid = size / 2;
-
-    for row in data.chunks_mut(size) {
-        row.rotate_left(mid);
+    // Same as noise, reusing the plans and kernel spectra of earlier calls
+    #[allow(clippy::too_many_arguments)]
+    pub fn noise(
+        &mut self,
+        input: &HeightBuffer,
+        output: &mut HeightBuffer,
+        amount: f32,
+        tri_threshold: f32,
+        pixel_distance: f32,
+        low: f32,
+        high: f32,
+        fade_low: f32,
+        fade_high: f32,
+        shape: &str,
+        seed: u32,
+        offset_x: i32,
+        offset_y: i32,
+        border: &str,
+        border_value: f32,
+        mask: &EffectMask,
+    ) -> Result<(), JsError> {
+        validate_noise("noise", input, output, amount, tri_threshold, pixel_distance)?;
+        let band = band("noise", low, high, fade_low, fade_high, fade_shape("noise", shape)?)?;
+        let border = border_mode("noise", border, border_value)?;
+        let mask = effect_mask("noise", input, mask)?;
+
+        let random = |x: i64, y: i64| unit_float(pixel_hash(seed as u64, x, y));
+        self.masked_noise(input, output, mask.as_ref(), amount, tri_threshold, pixel_distance, &band, (offset_x, offset_y), &border, random);
+        Ok(())
     }
 
-    rotate_rows(data, size, -(mid as isize));
-}
-
-/*
-fn ifft_shift_2d(data: &mut [Complex<f32>], size: usize) {
-    let mid = size / 2;
-
-    for row in data.chunks_mut(size) {
-        for i in 0..mid {
-            row.swap(i, i + mid);
+    // Same as coherent_noise, reusing the plans and kernel spectra of earlier calls
+    #[allow(clippy::too_many_arguments)]
+    pub fn coherent_noise(
+        &mut self,
+        input: &HeightBuffer,
+        output: &mut HeightBuffer,
+        amount: f32,
+        tri_threshold: f32,
+        pixel_distance: f32,
+        low: f32,
+        high: f32,
+        fade_low: f32,
+        fade_high: f32,
+        shape: &str,
+        noise_type: &str,
+        wavelength: f32,
+        octaves: u32,
+        lacunarity: f32,
+        gain: f32,
+        seed: u32,
+        offset_x: i32,
+        offset_y: i32,
+        border: &str,
+        border_value: f32,
+        mask: &EffectMask,
+    ) -> Result<(), JsError> {
+        validate_noise("coherent_noise", input, output, amount, tri_threshold, pixel_distance)?;
+        let band = band("coherent_noise", low, high, fade_low, fade_high, fade_shape("coherent_noise", shape)?)?;
+        let noise_type = NoiseType::from_name(noise_type)
+            .ok_or_else(|| JsError::new("coherent_noise: Invalid noise type"))?;
+        if !(wavelength.is_finite() && wavelength > 0.0) {
+            return Err(JsError::new("coherent_noise: Wavelength must be a positive number"));
+        }
+        if octaves == 0 || octaves > 32 {
+            return Err(JsError::new("coherent_noise: Octaves must be between 1 and 32"));
         }
+        if !(lacunarity.is_finite() && lacunarity > 0.0) {
+            return Err(JsError::new("coherent_noise: Lacunarity must be a positive number"));
+        }
+        if !(gain.is_finite() && gain >= 0.0) {
+            return Err(JsError::new("coherent_noise: Gain must be a non-negative number"));
+        }
+        let border = border_mode("coherent_noise", border, border_value)?;
+        let mask = effect_mask("coherent_noise", input, mask)?;
+
+        let fractal = Fractal { noise_type, octaves, lacunarity: lacunarity as f64, gain: gain as f64, seed };
+        // Pixel centers in units of the base wavelength
+        let scale = pixel_distance as f64 / wavelength as f64;
+        let value = |x: i64, y: i64| fractal.sample((x as f64 + 0.5) * scale, (y as f64 + 0.5) * scale);
+        self.masked_noise(input, output, mask.as_ref(), amount, tri_threshold, pixel_distance, &band, (offset_x, offset_y), &border, value);
+        Ok(())
     }
+}
 
-    for col in 0..size {
-        for i in 0..mid {
-            data.swap(i * size + col, (i + mid) * size + col);
+impl EffectsContext {
+    // Writes value(x, y) * amount to `output`, masked by the elevation band combined with `mask` and,
+    // if `tri_threshold` > 0, by the blurred TRI mask. (x, y) is the position of the pixel in the whole map.
+    #[allow(clippy::too_many_arguments)]
+    fn masked_noise(
+        &mut self,
+        input: &HeightBuffer,
+        output: &mut HeightBuffer,
+        mask: Option<&Mask>,
+        amount: f32,
+        tri_threshold: f32,
+        pixel_distance: f32,
+        band: &Band,
+        (offset_x, offset_y): (i32, i32),
+        border: &BorderMode,
+        value: impl Fn(i64, i64) -> f32 + Sync,
+    ) {
+        let (width, height) = (input.width(), input.height());
+        let input_slice = input.data();
+        let output_slice = output.data_mut();
+        let value = |i: usize| value(offset_x as i64 + (i % width) as i64, offset_y as i64 + (i / width) as i64);
+
+        if tri_threshold == 0.0 {
+            output_slice.par_iter_mut()
+                .enumerate()
+                .for_each(|(i, output_value)| {
+                    let elevation_alpha = effect_alpha(mask, i, band.alpha(input_slice[i]));
+                    *output_value = value(i) * amount * elevation_alpha;
+                });
+        } else {
+            let tri = terrain::tri_inside(input_slice, width, height);
+
+            let tri_mask: Vec<f32> = tri
+                .par_iter()
+                .map(|&v| if v >= tri_threshold { 1.0 } else { 0.0 })
+                .collect();
+
+            let tri_mask = self.blur(&tri_mask, width, height, tri_blur_radius(amount, pixel_distance), border);
+
+            output_slice.par_iter_mut()
+                .enumerate()
+                .for_each(|(i, output_value)| {
+                    let elevation_alpha = effect_alpha(mask, i, band.alpha(input_slice[i]));
+                    *output_value = value(i) * amount * tri_mask[i] * elevation_alpha;
+                });
         }
     }
 }
-*/
-
-fn generate_gaussian_kernel(size: usize, radius: f32) -> Vec<Complex<f32>> {
-    let sigma = (radius - 1.0) * 0.3 + 0.8;
-    let length = size * size;
-    let mut kernel = vec![Complex::new(0.0, 0.0); length];
-    let center = (size - 1) as f32 / 2.0;
-    let sigma_squared = sigma * sigma;
-    let two_pi_sigma_squared = 2.0 * std::f32::consts::PI * sigma_squared;
-
-    let mut sum = 0.0;
-    for y in 0..size {
-        let dy = y as f32 - center;
-        for x in 0..size {
-            let dx = x as f32 - center;
-            let distance_squared = (dx * dx + dy * dy)  as f32;
-
-            let value = (1.0 / two_pi_sigma_squared) * 
-                (-distance_squared / (2.0 * sigma_squared)).exp();
-
-            sum += value;
-            kernel[y * size + x] = Complex::new(value, 0.0);
-        }
+
+fn validate_pixel_size(name: &str, pixel_size: f32) -> Result<(), JsError> {
+    if !(pixel_size.is_finite() && pixel_size > 0.0) {
+        return Err(JsError::new(&format!("{}: Pixel size must be a positive number", name)));
     }
+    Ok(())
+}
 
-    kernel.par_iter_mut().for_each(|k| {
-        k.re /= sum;
-    });
+fn slope_method(name: &str, method: &str) -> Result<SlopeMethod, JsError> {
+    SlopeMethod::from_name(method)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid method", name)))
+}
 
-    kernel
+// method: "horn" | "zevenbergen-thorne", unit: "degrees" | "percent"
+// `pixel_size` is the size of a pixel in metres (unitSize).
+#[wasm_bindgen]
+pub fn slope(input: &HeightBuffer, output: &mut HeightBuffer, pixel_size: f32, method: &str, unit: &str, border: &str, border_value: f32) -> Result<(), JsError> {
+    validate_size("slope", input, output)?;
+    validate_pixel_size("slope", pixel_size)?;
+    let method = slope_method("slope", method)?;
+    let unit = SlopeUnit::from_name(unit)
+        .ok_or_else(|| JsError::new("slope: Invalid unit"))?;
+    let border = border_mode("slope", border, border_value)?;
+
+    let result = terrain::slope(input.data(), input.width(), input.height(), pixel_size, method, unit, &border);
+    output.data_mut().copy_from_slice(&result);
+    Ok(())
 }
 
-// Calculation of Terrain Ruggedness Index considering edge parts
-fn calculate_tri(dem: &[f32], size: usize) -> Vec<f32> {
-    let mut tri = vec![0.0; dem.len()];
-
-    tri.par_iter_mut().enumerate().for_each(|(i, tri_value)| {
-        let row = i / size;
-        let col = i % size;
-        let mut sum = 0.0;
-        let mut count = 0;
-
-        for dy in -1..=1 {
-            for dx in -1..=1 {
-                if dx == 0 && dy == 0 {
-                    continue;
-                }
-
-                let neighbor_row = row as i32 + dy;
-                let neighbor_col = col as i32 + dx;
-
-                if neighbor_row >= 0 && neighbor_row < size as i32 &&
-                   neighbor_col >= 0 && neighbor_col < size as i32 {
-                    let neighbor_idx = (neighbor_row * size as i32 + neighbor_col) as usize;
-                    let elevation_diff = (dem[i] - dem[neighbor_idx]).abs();
-                    sum += elevation_diff;
-                    count += 1;
-                }
-            }
-        }
+// Degrees clockwise from north that the slope faces, -1 on flat pixels.
+// method: "horn" | "zevenbergen-thorne"
+#[wasm_bindgen]
+pub fn aspect(input: &HeightBuffer, output: &mut HeightBuffer, pixel_size: f32, method: &str, border: &str, border_value: f32) -> Result<(), JsError> {
+    validate_size("aspect", input, output)?;
+    validate_pixel_size("aspect", pixel_size)?;
+    let method = slope_method("aspect", method)?;
+    let border = border_mode("aspect", border, border_value)?;
+
+    let result = terrain::aspect(input.data(), input.width(), input.height(), pixel_size, method, &border);
+    output.data_mut().copy_from_slice(&result);
+    Ok(())
+}
 
-        *tri_value = sum / count as f32;
-    });
+// curvature_type: "profile" | "plan", in 1/m
+#[wasm_bindgen]
+pub fn curvature(input: &HeightBuffer, output: &mut HeightBuffer, pixel_size: f32, curvature_type: &str, border: &str, border_value: f32) -> Result<(), JsError> {
+    validate_size("curvature", input, output)?;
+    validate_pixel_size("curvature", pixel_size)?;
+    let curvature_type = CurvatureType::from_name(curvature_type)
+        .ok_or_else(|| JsError::new("curvature: Invalid curvature type"))?;
+    let border = border_mode("curvature", border, border_value)?;
+
+    let result = terrain::curvature(input.data(), input.width(), input.height(), pixel_size, curvature_type, &border);
+    output.data_mut().copy_from_slice(&result);
+    Ok(())
+}
+
+// Topographic Position Index over the (2 * radius + 1)^2 pixel square around every pixel, in metres.
+// The radius may not exceed the larger side of the map.
+#[wasm_bindgen]
+pub fn tpi(input: &HeightBuffer, output: &mut HeightBuffer, radius: usize, border: &str, border_value: f32) -> Result<(), JsError> {
+    validate_size("tpi", input, output)?;
+    if radius == 0 || radius > input.width().max(input.height()) {
+        return Err(JsError::new("tpi: Radius must be between 1 and the larger side of the map"));
+    }
+    let border = border_mode("tpi", border, border_value)?;
 
-    tri
+    let result = terrain::tpi(input.data(), input.width(), input.height(), radius, &border);
+    output.data_mut().copy_from_slice(&result);
+    Ok(())
 }
 
-fn get_ll_alpha(value: f32, threshold: f32, fade: f32) -> f32 {
-    let lower = threshold;
-    let upper = threshold + fade;
+// Terrain Ruggedness Index in metres: mean absolute difference to the 8 neighbours
+#[wasm_bindgen]
+pub fn tri(input: &HeightBuffer, output: &mut HeightBuffer, border: &str, border_value: f32) -> Result<(), JsError> {
+    validate_size("tri", input, output)?;
+    let border = border_mode("tri", border, border_value)?;
 
-    if value >= upper {
-        return 1.0;
-    }
+    let result = terrain::tri(input.data(), input.width(), input.height(), &border);
+    output.data_mut().copy_from_slice(&result);
+    Ok(())
+}
 
-    if value <= lower {
-        return 0.0;
+// Shaded relief as RGBA bytes (width * height * 4) for previews.
+// mode: "single" | "multidirectional". `azimuth` (degrees clockwise from north) is only used by "single",
+// and `altitude` is the angle of the light above the horizon in degrees.
+// `exaggeration` multiplies the elevations. With `tint_blend` > 0, the shade is multiplied by a hypsometric
+// color ramp over the elevations [tint_min, tint_max].
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn hillshade(
+    input: &HeightBuffer,
+    pixel_size: f32,
+    exaggeration: f32,
+    mode: &str,
+    azimuth: f32,
+    altitude: f32,
+    tint_blend: f32,
+    tint_min: f32,
+    tint_max: f32,
+    border: &str,
+    border_value: f32,
+) -> Result<Vec<u8>, JsError> {
+    validate_pixel_size("hillshade", pixel_size)?;
+    if !(exaggeration.is_finite() && exaggeration > 0.0) {
+        return Err(JsError::new("hillshade: Exaggeration must be a positive number"));
     }
+    validate_number("hillshade", "Azimuth", azimuth)?;
+    if !(altitude.is_finite() && (0.0..=90.0).contains(&altitude)) {
+        return Err(JsError::new("hillshade: Altitude must be between 0 and 90 degrees"));
+    }
+    let mode = ShadeMode::from_name(mode, azimuth, altitude)
+        .ok_or_else(|| JsError::new("hillshade: Invalid mode"))?;
+    if !(tint_blend.is_finite() && (0.0..=1.0).contains(&tint_blend)) {
+        return Err(JsError::new("hillshade: Tint blend must be between 0 and 1"));
+    }
+    if tint_blend > 0.0 && !(tint_min.is_finite() && tint_max.is_finite() && tint_min < tint_max) {
+        return Err(JsError::new("hillshade: Tint range must be finite and tint_min must be less than tint_max"));
+    }
+    let border = border_mode("hillshade", border, border_value)?;
 
-    (value - lower) / fade
+    let (width, height) = (input.width(), input.height());
+    let shade = relief::shade(input.data(), width, height, pixel_size, exaggeration, mode, &border);
+    Ok(relief::render(input.data(), &shade, &Tint { blend: tint_blend, min: tint_min, max: tint_max }))
 }
 
-fn get_ul_alpha(value: f32, threshold: f32, fade: f32) -> f32 {
-    let lower = threshold - fade;
-    let upper = threshold;
+// Mask for the effects: 1 where the elevation is in [low, high], falling to 0 over
+// `fade_low` metres below the range and `fade_high` metres above it.
+// `shape` is the curve of both fades (see fade_shape).
+// As an EffectMask with mode "multiply", this combines a second elevation band with the band of an effect.
+#[wasm_bindgen]
+pub fn elevation_mask(input: &HeightBuffer, output: &mut HeightBuffer, low: f32, high: f32, fade_low: f32, fade_high: f32, shape: &str) -> Result<(), JsError> {
+    validate_size("elevation_mask", input, output)?;
+    let shape = fade_shape("elevation_mask", shape)?;
+    let band = band("elevation_mask", low, high, fade_low, fade_high, shape)?;
 
-    if value >= upper {
-        return 0.0;
-    }
+    output.data_mut().copy_from_slice(&mask::elevation_band(input.data(), &band));
+    Ok(())
+}
 
-    if value <= lower {
-        return 1.0;
+// Mask for the effects: 1 where the Horn slope is in [low, high] degrees,
+// falling linearly to 0 over `fade` degrees outside the range.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn slope_mask(input: &HeightBuffer, output: &mut HeightBuffer, pixel_size: f32, low: f32, high: f32, fade: f32, border: &str, border_value: f32) -> Result<(), JsError> {
+    validate_size("slope_mask", input, output)?;
+    validate_pixel_size("slope_mask", pixel_size)?;
+    let band = band("slope_mask", low, high, fade, fade, FadeShape::Linear)?;
+    let border = border_mode("slope_mask", border, border_value)?;
+
+    let (width, height) = (input.width(), input.height());
+    output.data_mut().copy_from_slice(&mask::slope_band(input.data(), width, height, pixel_size, &band, &border));
+    Ok(())
+}
+
+// Mask for the effects from a water map (1 on land, 0 on water): 1 on land closer than
+// `distance` pixels to water, falling linearly to 0 over `fade` pixels beyond it.
+// Water pixels are 1 if `include_water` is set, otherwise 0.
+#[wasm_bindgen]
+pub fn water_mask(water_map: &HeightBuffer, output: &mut HeightBuffer, distance: f32, fade: f32, include_water: bool) -> Result<(), JsError> {
+    validate_size("water_mask", water_map, output)?;
+    if !(distance.is_finite() && distance >= 0.0) {
+        return Err(JsError::new("water_mask: Distance must be a non-negative number"));
     }
+    let band = band("water_mask", 0.0, distance, 0.0, fade, FadeShape::Linear)?;
 
-    (upper - value) / fade
+    let (width, height) = (water_map.width(), water_map.height());
+    output.data_mut().copy_from_slice(&mask::water_proximity(water_map.data(), width, height, &band, include_water));
+    Ok(())
 }
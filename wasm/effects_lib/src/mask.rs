@@ -0,0 +1,162 @@
+use rayon::prelude::*;
+use wasm_bindgen::prelude::*;
+use crate::border::BorderMode;
+use crate::buffer::HeightBuffer;
+use crate::ramp::Band;
+use crate::terrain::{slope, SlopeMethod, SlopeUnit};
+
+#[derive(Clone, Copy)]
+pub enum MaskMode {
+    // The mask scales the elevation band of the effect
+    Multiply,
+    // The mask is used instead of the elevation band
+    Replace,
+}
+
+impl MaskMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "multiply" => Some(Self::Multiply),
+            "replace" => Some(Self::Replace),
+            _ => None,
+        }
+    }
+}
+
+// Per-pixel alpha of an effect, 0 to 1
+pub struct Mask<'a> {
+    pub data: &'a [f32],
+    pub mode: MaskMode,
+}
+
+impl Mask<'_> {
+    pub fn alpha(&self, i: usize, elevation_alpha: f32) -> f32 {
+        let m = self.data[i].clamp(0.0, 1.0);
+        match self.mode {
+            MaskMode::Multiply => m * elevation_alpha,
+            MaskMode::Replace => m,
+        }
+    }
+}
+
+// Per-pixel alpha passed to the effects, built once from a mask buffer (e.g. from elevation_mask)
+// and reused for every effect that shares it. EffectMask.none() leaves only the elevation band.
+#[wasm_bindgen]
+pub struct EffectMask {
+    alpha: Option<(HeightBuffer, MaskMode)>,
+}
+
+#[wasm_bindgen]
+impl EffectMask {
+    // mode: "multiply" | "replace". Takes ownership of `alpha`, 0 to 1 per pixel.
+    #[wasm_bindgen(constructor)]
+    pub fn new(alpha: HeightBuffer, mode: &str) -> Result<EffectMask, JsError> {
+        let mode = MaskMode::from_name(mode).ok_or_else(|| JsError::new("EffectMask.new: Invalid mask mode"))?;
+        Ok(EffectMask { alpha: Some((alpha, mode)) })
+    }
+
+    pub fn none() -> EffectMask {
+        EffectMask { alpha: None }
+    }
+}
+
+impl EffectMask {
+    pub fn alpha(&self) -> Option<(&HeightBuffer, MaskMode)> {
+        self.alpha.as_ref().map(|(alpha, mode)| (alpha, *mode))
+    }
+}
+
+// Elevation band combined with the optional mask
+pub fn effect_alpha(mask: Option<&Mask>, i: usize, elevation_alpha: f32) -> f32 {
+    mask.map_or(elevation_alpha, |mask| mask.alpha(i, elevation_alpha))
+}
+
+pub fn elevation_band(data: &[f32], band: &Band) -> Vec<f32> {
+    data.par_iter().map(|&v| band.alpha(v)).collect()
+}
+
+// Band of the slope in degrees
+pub fn slope_band(data: &[f32], width: usize, height: usize, pixel_size: f32, band: &Band, border: &BorderMode) -> Vec<f32> {
+    let mut degrees = slope(data, width, height, pixel_size, SlopeMethod::Horn, SlopeUnit::Degrees, border);
+    degrees.par_iter_mut().for_each(|v| *v = band.alpha(*v));
+    degrees
+}
+
+// Squared distance of pixels that have no water in reach, large enough to be beyond any fade
+const FAR: f32 = 1e20;
+
+// Squared euclidean distance transform of one line (Felzenszwalb and Huttenlocher).
+// `f` holds 0 on water pixels and FAR elsewhere, `v` and `z` are scratch space.
+fn distance_transform_line(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let parabola = |q: usize| f[q] + (q * q) as f32;
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = f32::NEG_INFINITY;
+    z[1] = f32::INFINITY;
+
+    for q in 1..f.len() {
+        let mut s = (parabola(q) - parabola(v[k])) / (2 * (q - v[k])) as f32;
+        while s <= z[k] {
+            k -= 1;
+            s = (parabola(q) - parabola(v[k])) / (2 * (q - v[k])) as f32;
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    for (q, value) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        *value = (q as f32 - v[k] as f32).powi(2) + f[v[k]];
+    }
+}
+
+// Distance in pixels from every pixel to the nearest water pixel (water map value < 0.5)
+fn distance_to_water(water_map: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let scratch = |len: usize| move || (vec![0.0; len], vec![0; len], vec![0.0; len + 1]);
+
+    // Columns
+    let mut columns = vec![0.0; width * height];
+    columns.par_chunks_exact_mut(height)
+        .enumerate()
+        .for_each_init(scratch(height), |(f, v, z), (x, column)| {
+            for (y, value) in f.iter_mut().enumerate() {
+                *value = if water_map[y * width + x] < 0.5 { 0.0 } else { FAR };
+            }
+            distance_transform_line(f, column, v, z);
+        });
+
+    // Rows
+    let mut result = vec![0.0; width * height];
+    result.par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each_init(scratch(width), |(f, v, z), (y, row)| {
+            for (x, value) in f.iter_mut().enumerate() {
+                *value = columns[x * height + y];
+            }
+            distance_transform_line(f, row, v, z);
+            row.iter_mut().for_each(|d| *d = d.sqrt());
+        });
+
+    result
+}
+
+// `band` of the distance to water in pixels on land. Water pixels are 1 with `include_water`, otherwise 0.
+// The water map is 1 on land and 0 on water.
+pub fn water_proximity(water_map: &[f32], width: usize, height: usize, band: &Band, include_water: bool) -> Vec<f32> {
+    let mut result = distance_to_water(water_map, width, height);
+    result.par_iter_mut()
+        .zip(water_map.par_iter())
+        .for_each(|(d, &water)| {
+            *d = if water < 0.5 {
+                if include_water { 1.0 } else { 0.0 }
+            } else {
+                band.alpha(*d)
+            };
+        });
+    result
+}
@@ -0,0 +1,72 @@
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy)]
+pub enum FadeShape {
+    Linear,
+    // 3t^2 - 2t^3, flat at both ends of the fade
+    Smoothstep,
+    // 6t^5 - 15t^4 + 10t^3, also without a jump in curvature
+    Smootherstep,
+    // Half a cosine wave
+    Cosine,
+}
+
+impl FadeShape {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Self::Linear),
+            "smoothstep" => Some(Self::Smoothstep),
+            "smootherstep" => Some(Self::Smootherstep),
+            "cosine" => Some(Self::Cosine),
+            _ => None,
+        }
+    }
+
+    // Maps t in [0, 1] to [0, 1]
+    pub fn ease(&self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Self::Smootherstep => t * t * t * (t * (t * 6.0 - 15.0) + 10.0),
+            Self::Cosine => 0.5 - 0.5 * (PI * t).cos(),
+        }
+    }
+}
+
+// Alpha window over a value: 0 up to `start`, rising to 1 at `low`, 1 up to `high`,
+// and falling to 0 at `end`. Infinite edges leave that side open.
+// A value on a hard step (start == low or high == end) belongs to the side above it.
+pub struct Band {
+    pub start: f32,
+    pub low: f32,
+    pub high: f32,
+    pub end: f32,
+    pub shape: FadeShape,
+}
+
+impl Band {
+    // [low, high] with fades of `fade_low` below it and `fade_high` above it
+    pub fn new(low: f32, high: f32, fade_low: f32, fade_high: f32, shape: FadeShape) -> Self {
+        Self { start: low - fade_low, low, high, end: high + fade_high, shape }
+    }
+
+    pub fn alpha(&self, value: f32) -> f32 {
+        let rising = if value >= self.low {
+            1.0
+        } else if value <= self.start {
+            0.0
+        } else {
+            self.shape.ease((value - self.start) / (self.low - self.start))
+        };
+
+        let falling = if value >= self.end {
+            0.0
+        } else if value <= self.high {
+            1.0
+        } else {
+            self.shape.ease((self.end - value) / (self.end - self.high))
+        };
+
+        rising * falling
+    }
+}
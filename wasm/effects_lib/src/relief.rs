@@ -0,0 +1,100 @@
+use rayon::prelude::*;
+use crate::border::BorderMode;
+use crate::terrain::{gradient, map_windows, SlopeMethod};
+
+#[derive(Clone, Copy)]
+pub enum ShadeMode {
+    // One light source. Azimuth in degrees clockwise from north, altitude in degrees above the horizon.
+    Single { azimuth: f32, altitude: f32 },
+    // Lights from 225, 270, 315 and 360 degrees, each weighted by how much the slope faces across it (MDOW).
+    // Slopes are lit from every direction, so no landform is lost in the shadow.
+    Multidirectional { altitude: f32 },
+}
+
+impl ShadeMode {
+    // `azimuth` is only used by "single".
+    pub fn from_name(name: &str, azimuth: f32, altitude: f32) -> Option<Self> {
+        match name {
+            "single" => Some(Self::Single { azimuth, altitude }),
+            "multidirectional" => Some(Self::Multidirectional { altitude }),
+            _ => None,
+        }
+    }
+}
+
+// Lambertian shade in [0, 1] of the surface normal (nx, ny, nz) (east, north, up) lit from `azimuth`
+fn lambert(normal: (f32, f32, f32), azimuth: f32, altitude: f32) -> f32 {
+    let (azimuth, altitude) = (azimuth.to_radians(), altitude.to_radians());
+    let light = (azimuth.sin() * altitude.cos(), azimuth.cos() * altitude.cos(), altitude.sin());
+    (normal.0 * light.0 + normal.1 * light.1 + normal.2 * light.2).max(0.0)
+}
+
+// Shade in [0, 1] of every pixel. `exaggeration` multiplies the elevations.
+pub fn shade(data: &[f32], width: usize, height: usize, pixel_size: f32, exaggeration: f32, mode: ShadeMode, border: &BorderMode) -> Vec<f32> {
+    map_windows(data, width, height, border, |z| {
+        let (dx, dy) = gradient(z, pixel_size, SlopeMethod::Horn);
+        let (dx, dy) = (dx * exaggeration, dy * exaggeration);
+        // dy points south, so the north component of the normal is +dy
+        let length = (dx * dx + dy * dy + 1.0).sqrt();
+        let normal = (-dx / length, dy / length, 1.0 / length);
+
+        match mode {
+            ShadeMode::Single { azimuth, altitude } => lambert(normal, azimuth, altitude),
+            ShadeMode::Multidirectional { altitude } => {
+                // Aspect of the downhill direction, 0 on flat pixels
+                let aspect = (0.0 - dx).atan2(dy);
+                // sin^2 weights of the 4 azimuths add up to 2
+                [225.0f32, 270.0, 315.0, 360.0].iter()
+                    .map(|&azimuth| (aspect - azimuth.to_radians()).sin().powi(2) * lambert(normal, azimuth, altitude))
+                    .sum::<f32>() / 2.0
+            }
+        }
+    })
+}
+
+// Color of `t` in [0, 1] from lowland green through tan and brown to snow white
+fn hypsometric_color(t: f32) -> [f32; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [112.0, 153.0, 89.0],
+        [179.0, 191.0, 120.0],
+        [222.0, 204.0, 140.0],
+        [181.0, 140.0, 99.0],
+        [245.0, 245.0, 245.0],
+    ];
+    let position = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (position as usize).min(STOPS.len() - 2);
+    let f = position - i as f32;
+    let (a, b) = (STOPS[i], STOPS[i + 1]);
+    [a[0] + (b[0] - a[0]) * f, a[1] + (b[1] - a[1]) * f, a[2] + (b[2] - a[2]) * f]
+}
+
+pub struct Tint {
+    // 0 is a gray hillshade, 1 fully multiplies the shade by the tint
+    pub blend: f32,
+    // Elevations mapped to the start and the end of the color ramp
+    pub min: f32,
+    pub max: f32,
+}
+
+// RGBA pixels of the shade, optionally multiplied by the hypsometric tint of the elevations
+pub fn render(data: &[f32], shade: &[f32], tint: &Tint) -> Vec<u8> {
+    let mut rgba = vec![0u8; shade.len() * 4];
+    let range = tint.max - tint.min;
+
+    rgba.par_chunks_exact_mut(4)
+        .zip(shade.par_iter().zip(data.par_iter()))
+        .for_each(|(pixel, (&shade, &elevation))| {
+            let color = if tint.blend > 0.0 {
+                hypsometric_color((elevation - tint.min) / range)
+            } else {
+                [255.0; 3]
+            };
+            for (channel, c) in pixel.iter_mut().zip(color) {
+                let c = 255.0 + (c - 255.0) * tint.blend;
+                *channel = (c * shade).round().clamp(0.0, 255.0) as u8;
+            }
+            pixel[3] = 255;
+        });
+
+    rgba
+}
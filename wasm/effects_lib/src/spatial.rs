@@ -0,0 +1,49 @@
+use rayon::prelude::*;
+use crate::border::{add_padding, BorderMode};
+use crate::gaussian::{gaussian_reach, gaussian_sigma};
+
+// Normalized 1D Gaussian of `radius` with 2 * gaussian_reach + 1 taps
+pub fn gaussian_weights(radius: f32) -> Vec<f32> {
+    let sigma = gaussian_sigma(radius);
+    let reach = gaussian_reach(radius) as isize;
+    let weights: Vec<f32> = (-reach..=reach)
+        .map(|d| (-((d * d) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    weights.iter().map(|w| w / sum).collect()
+}
+
+// Gaussian blur as a horizontal and a vertical 1D convolution.
+// Gives the same result as fft::fft_blur with the same radius and border,
+// and costs O(reach) per pixel instead of the FFT of the whole map.
+pub fn separable_blur(data: &[f32], width: usize, height: usize, radius: f32, border: &BorderMode) -> Vec<f32> {
+    let k = gaussian_weights(radius);
+    let padding = gaussian_reach(radius);
+    let padded_width = width + 2 * padding;
+    let padded = add_padding(data, width, height, padding, border);
+
+    // Horizontal pass, keeping the padding rows for the vertical pass
+    let mut temp = vec![0.0; width * (height + 2 * padding)];
+    temp.par_chunks_exact_mut(width)
+        .zip(padded.par_chunks_exact(padded_width))
+        .for_each(|(row, src)| {
+            for (value, window) in row.iter_mut().zip(src.windows(k.len())) {
+                *value = k.iter().zip(window).map(|(w, v)| w * v).sum();
+            }
+        });
+
+    // Vertical pass
+    let mut result = vec![0.0; width * height];
+    result.par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = k.iter()
+                    .enumerate()
+                    .map(|(j, w)| w * temp[(y + j) * width + x])
+                    .sum();
+            }
+        });
+
+    result
+}
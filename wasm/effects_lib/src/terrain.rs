@@ -0,0 +1,230 @@
+use rayon::prelude::*;
+use crate::border::{add_padding, BorderMode};
+
+// Terrain analysis of a heightmap in metres. x points east and y (the row index) points south.
+
+#[derive(Clone, Copy)]
+pub enum SlopeMethod {
+    // 3x3 weighted differences, smoother on noisy data
+    Horn,
+    // Central differences of the 4 direct neighbours
+    ZevenbergenThorne,
+}
+
+impl SlopeMethod {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "horn" => Some(Self::Horn),
+            "zevenbergen-thorne" => Some(Self::ZevenbergenThorne),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum SlopeUnit {
+    Degrees,
+    Percent,
+}
+
+impl SlopeUnit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "degrees" => Some(Self::Degrees),
+            "percent" => Some(Self::Percent),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum CurvatureType {
+    // Curvature along the slope direction. Positive where the slope gets steeper downhill (convex),
+    // negative where it flattens out (concave).
+    Profile,
+    // Curvature across the slope direction. Negative where the contours spread out downhill
+    // (ridges, spurs, hilltops), positive where they converge (valleys).
+    Plan,
+}
+
+impl CurvatureType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "profile" => Some(Self::Profile),
+            "plan" => Some(Self::Plan),
+            _ => None,
+        }
+    }
+}
+
+// Calls f with the 3x3 window around every pixel, in row-major order (z[0] is north-west, z[8] south-east).
+pub fn map_windows(data: &[f32], width: usize, height: usize, border: &BorderMode, f: impl Fn(&[f32; 9]) -> f32 + Sync) -> Vec<f32> {
+    let padded_width = width + 2;
+    let padded = add_padding(data, width, height, 1, border);
+    let mut result = vec![0.0; width * height];
+
+    result.par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let rows = [y * padded_width, (y + 1) * padded_width, (y + 2) * padded_width];
+            for (x, value) in row.iter_mut().enumerate() {
+                let mut z = [0.0; 9];
+                for (i, offset) in rows.iter().enumerate() {
+                    z[i * 3..i * 3 + 3].copy_from_slice(&padded[offset + x..offset + x + 3]);
+                }
+                *value = f(&z);
+            }
+        });
+
+    result
+}
+
+// dz/dx (east) and dz/dy (south) of the window
+pub fn gradient(z: &[f32; 9], pixel_size: f32, method: SlopeMethod) -> (f32, f32) {
+    match method {
+        SlopeMethod::Horn => (
+            ((z[2] + 2.0 * z[5] + z[8]) - (z[0] + 2.0 * z[3] + z[6])) / (8.0 * pixel_size),
+            ((z[6] + 2.0 * z[7] + z[8]) - (z[0] + 2.0 * z[1] + z[2])) / (8.0 * pixel_size),
+        ),
+        SlopeMethod::ZevenbergenThorne => (
+            (z[5] - z[3]) / (2.0 * pixel_size),
+            (z[7] - z[1]) / (2.0 * pixel_size),
+        ),
+    }
+}
+
+pub fn slope(data: &[f32], width: usize, height: usize, pixel_size: f32, method: SlopeMethod, unit: SlopeUnit, border: &BorderMode) -> Vec<f32> {
+    map_windows(data, width, height, border, |z| {
+        let (dx, dy) = gradient(z, pixel_size, method);
+        let rise = (dx * dx + dy * dy).sqrt();
+        match unit {
+            SlopeUnit::Degrees => rise.atan().to_degrees(),
+            SlopeUnit::Percent => rise * 100.0,
+        }
+    })
+}
+
+// Compass direction the slope faces, in degrees clockwise from north in [0, 360). Flat pixels are -1.
+pub fn aspect(data: &[f32], width: usize, height: usize, pixel_size: f32, method: SlopeMethod, border: &BorderMode) -> Vec<f32> {
+    map_windows(data, width, height, border, |z| {
+        let (dx, dy) = gradient(z, pixel_size, method);
+        if dx == 0.0 && dy == 0.0 {
+            return -1.0;
+        }
+        // Downhill is (-dx) east and (+dy) north. 0.0 - dx avoids -0 on north facing slopes.
+        (0.0 - dx).atan2(dy).to_degrees().rem_euclid(360.0)
+    })
+}
+
+// Zevenbergen-Thorne curvature in 1/m. Flat pixels are 0.
+pub fn curvature(data: &[f32], width: usize, height: usize, pixel_size: f32, curvature_type: CurvatureType, border: &BorderMode) -> Vec<f32> {
+    let l = pixel_size;
+    map_windows(data, width, height, border, |z| {
+        let d = ((z[3] + z[5]) / 2.0 - z[4]) / (l * l);
+        let e = ((z[1] + z[7]) / 2.0 - z[4]) / (l * l);
+        let f = (-z[0] + z[2] + z[6] - z[8]) / (4.0 * l * l);
+        let g = (z[5] - z[3]) / (2.0 * l);
+        // dz/dy with y pointing north
+        let h = (z[1] - z[7]) / (2.0 * l);
+
+        let gradient_squared = g * g + h * h;
+        if gradient_squared == 0.0 {
+            return 0.0;
+        }
+        match curvature_type {
+            CurvatureType::Profile => -2.0 * (d * g * g + e * h * h + f * g * h) / gradient_squared,
+            CurvatureType::Plan => 2.0 * (d * h * h + e * g * g - f * g * h) / gradient_squared,
+        }
+    })
+}
+
+// Topographic Position Index: elevation minus the mean of the other pixels in the
+// (2 * radius + 1)^2 square around it. Positive on ridges and hilltops, negative in valleys.
+pub fn tpi(data: &[f32], width: usize, height: usize, radius: usize, border: &BorderMode) -> Vec<f32> {
+    let size = 2 * radius + 1;
+    let outside = border.constant() as f64;
+
+    // Horizontal box sums
+    let mut row_sums = vec![0.0f64; width * height];
+    row_sums.par_chunks_exact_mut(width)
+        .zip(data.par_chunks_exact(width))
+        .for_each(|(sums, row)| window_sums(sums, radius, border, outside, |x| row[x] as f64));
+
+    // Vertical box sums of the horizontal ones, stored by column. A row outside the map in constant mode sums to `size` values.
+    let mut sums = vec![0.0f64; width * height];
+    sums.par_chunks_exact_mut(height)
+        .enumerate()
+        .for_each(|(x, sums)| window_sums(sums, radius, border, outside * size as f64, |y| row_sums[y * width + x]));
+
+    let count = ((size as f64).powi(2) - 1.0).max(1.0);
+    let mut result = vec![0.0; width * height];
+    result.par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, value) in row.iter_mut().enumerate() {
+                let center = data[y * width + x] as f64;
+                *value = (center - (sums[x * height + y] - center) / count) as f32;
+            }
+        });
+
+    result
+}
+
+// Sums of the 2 * radius + 1 values around every position of a line of `sums.len()` values.
+// `value(i)` reads position i in the line, positions outside read through `border` or as `outside` in constant mode.
+fn window_sums(sums: &mut [f64], radius: usize, border: &BorderMode, outside: f64, value: impl Fn(usize) -> f64) {
+    let len = sums.len();
+    let at = |i: isize| border.index(i, len).map_or(outside, &value);
+    let radius = radius as isize;
+
+    let mut sum: f64 = (-radius..radius).map(at).sum();
+    for (i, s) in sums.iter_mut().enumerate() {
+        let i = i as isize;
+        sum += at(i + radius);
+        *s = sum;
+        sum -= at(i - radius);
+    }
+}
+
+// Terrain Ruggedness Index: mean absolute difference to the 8 neighbours
+pub fn tri(data: &[f32], width: usize, height: usize, border: &BorderMode) -> Vec<f32> {
+    map_windows(data, width, height, border, |z| {
+        z.iter().map(|v| (v - z[4]).abs()).sum::<f32>() / 8.0
+    })
+}
+
+// Same as tri, but neighbours outside the map are left out instead of read through a border mode.
+// The TRI mask of noise uses it, so its output stays the same.
+pub fn tri_inside(dem: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let mut tri = vec![0.0; dem.len()];
+
+    tri.par_iter_mut().enumerate().for_each(|(i, tri_value)| {
+        let row = i / width;
+        let col = i % width;
+        let mut sum = 0.0;
+        let mut count = 0;
+
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+
+                let neighbor_row = row as i32 + dy;
+                let neighbor_col = col as i32 + dx;
+
+                if neighbor_row >= 0 && neighbor_row < height as i32 &&
+                   neighbor_col >= 0 && neighbor_col < width as i32 {
+                    let neighbor_idx = (neighbor_row * width as i32 + neighbor_col) as usize;
+                    let elevation_diff = (dem[i] - dem[neighbor_idx]).abs();
+                    sum += elevation_diff;
+                    count += 1;
+                }
+            }
+        }
+
+        *tri_value = if count > 0 { sum / count as f32 } else { 0.0 };
+    });
+
+    tri
+}
@@ -0,0 +1,83 @@
+// Border handling shared by tiles_lib and effects_lib.
+// Each crate includes this file with #[path], so both use the same modes and names.
+
+#[derive(Clone, Copy)]
+pub enum BorderMode {
+    // a a a | a b c d | d d d
+    Replicate,
+    // c b a | a b c d | d c b
+    Reflect,
+    // b c d | a b c d | a b c, for seamless tiling textures
+    Wrap,
+    // v v v | a b c d | v v v, e.g. sea level around an island
+    Constant(f32),
+}
+
+impl BorderMode {
+    // `value` is only used by "constant".
+    pub fn from_name(name: &str, value: f32) -> Option<Self> {
+        match name {
+            "replicate" => Some(Self::Replicate),
+            "reflect" => Some(Self::Reflect),
+            "wrap" => Some(Self::Wrap),
+            "constant" if value.is_finite() => Some(Self::Constant(value)),
+            _ => None,
+        }
+    }
+
+    // Index in [0, len) that position `i` reads from, or None if it reads the constant value.
+    // `len` must be greater than 0.
+    pub fn index(&self, i: isize, len: usize) -> Option<usize> {
+        let len = len as isize;
+        if (0..len).contains(&i) {
+            return Some(i as usize);
+        }
+
+        match self {
+            Self::Replicate => Some(i.clamp(0, len - 1) as usize),
+            Self::Reflect => {
+                let m = i.rem_euclid(2 * len);
+                Some(if m < len { m } else { 2 * len - 1 - m } as usize)
+            }
+            Self::Wrap => Some(i.rem_euclid(len) as usize),
+            Self::Constant(_) => None,
+        }
+    }
+
+    // Value outside the raster in constant mode, 0 for the other modes
+    pub fn constant(&self) -> f32 {
+        match self {
+            Self::Constant(value) => *value,
+            _ => 0.0,
+        }
+    }
+
+    // Value at (x, y) of a width x height raster extended by this mode
+    pub fn sample(&self, data: &[f32], width: usize, height: usize, x: isize, y: isize) -> f32 {
+        match (self.index(x, width), self.index(y, height)) {
+            (Some(x), Some(y)) => data[y * width + x],
+            _ => self.constant(),
+        }
+    }
+}
+
+// Extends a width x height raster by `padding` pixels on every side
+pub fn add_padding(data: &[f32], width: usize, height: usize, padding: usize, mode: &BorderMode) -> Vec<f32> {
+    let padded_width = width + 2 * padding;
+    let padded_height = height + 2 * padding;
+    let mut padded_data = Vec::with_capacity(padded_width * padded_height);
+
+    for y in 0..padded_height {
+        let y = y as isize - padding as isize;
+        if (0..height as isize).contains(&y) {
+            let row = &data[y as usize * width..(y as usize + 1) * width];
+            padded_data.extend((-(padding as isize)..0).map(|x| mode.sample(data, width, height, x, y)));
+            padded_data.extend_from_slice(row);
+            padded_data.extend((width as isize..(width + padding) as isize).map(|x| mode.sample(data, width, height, x, y)));
+        } else {
+            padded_data.extend((0..padded_width).map(|x| mode.sample(data, width, height, x as isize - padding as isize, y)));
+        }
+    }
+
+    padded_data
+}
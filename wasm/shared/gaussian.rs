@@ -0,0 +1,12 @@
+// Gaussian blur size shared by effects_lib, which blurs with it, and tiles_lib,
+// which sizes the tile halos so that the blur does not reach the cross-faded part.
+
+// Sigma of the blur `radius`
+pub fn gaussian_sigma(radius: f32) -> f32 {
+    (radius - 1.0) * 0.3 + 0.8
+}
+
+// Number of pixels beyond which the Gaussian of `radius` is negligible
+pub fn gaussian_reach(radius: f32) -> usize {
+    (3.0 * gaussian_sigma(radius)).ceil() as usize
+}
@@ -0,0 +1,22 @@
+// Counter-based random numbers shared by tiles_lib and effects_lib.
+// Every value is a hash of a key (e.g. a seed) and a global coordinate,
+// so the result does not depend on the thread count or on how the map is split into tiles.
+
+// Hash of the key and the global coordinate
+pub fn pixel_hash(key: u64, x: i64, y: i64) -> u64 {
+    let mut h = key ^ 0x9E37_79B9_7F4A_7C15;
+    h = splitmix64(h ^ x as u64);
+    splitmix64(h ^ y as u64)
+}
+
+// Unit float in [0, 1) from the upper 24 bits of the hash
+pub fn unit_float(hash: u64) -> f32 {
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn splitmix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
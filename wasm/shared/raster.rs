@@ -0,0 +1,17 @@
+// Raster size checks shared by the HeightBuffer of tiles_lib and effects_lib.
+
+use wasm_bindgen::prelude::*;
+
+// Number of f32 values in a width x height raster, or None if it does not fit in memory
+pub fn raster_length(width: usize, height: usize) -> Option<usize> {
+    width.checked_mul(height)
+        .filter(|length| length.checked_mul(std::mem::size_of::<f32>()).is_some())
+}
+
+pub fn checked_length(name: &str, width: usize, height: usize) -> Result<usize, JsError> {
+    if width == 0 || height == 0 {
+        return Err(JsError::new(&format!("{}: Width and height must be greater than 0", name)));
+    }
+    raster_length(width, height)
+        .ok_or_else(|| JsError::new(&format!("{}: Size is too large", name)))
+}
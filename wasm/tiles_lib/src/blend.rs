@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+use rayon::prelude::*;
+
+#[derive(Clone, Copy)]
+pub enum FeatherCurve {
+    Linear,
+    Smoothstep,
+    Cosine,
+}
+
+impl FeatherCurve {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Self::Linear),
+            "smoothstep" => Some(Self::Smoothstep),
+            "cosine" => Some(Self::Cosine),
+            _ => None,
+        }
+    }
+
+    // Maps t in [0, 1] to a weight in [0, 1]
+    fn apply(&self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Self::Cosine => 0.5 - 0.5 * (PI * t).cos(),
+        }
+    }
+}
+
+pub struct Placement {
+    pub offset_x: isize,
+    pub offset_y: isize,
+    // Width of the overlay border that is not copied as is. The feather lies inside this border.
+    pub inset: usize,
+    pub feather: usize,
+    pub curve: FeatherCurve,
+    // Use the euclidean distance to the core in the corners instead of the smaller axis weight
+    pub rounded_corners: bool,
+}
+
+// Distance in pixels from `i` to the core [start, end), 0 inside the core.
+fn core_distance(i: usize, start: usize, end: usize) -> usize {
+    if i < start {
+        start - i
+    } else if i >= end {
+        i + 1 - end
+    } else {
+        0
+    }
+}
+
+// Blends `overlay` into `base` in place. The overlay core replaces the base,
+// the feather ring around it fades from the overlay to the base,
+// and `mask` (same size as the overlay, 0 to 1) scales the overlay weight.
+pub fn blend_maps(
+    base: &mut [f32],
+    base_width: usize,
+    overlay: &[f32],
+    overlay_width: usize,
+    overlay_height: usize,
+    placement: &Placement,
+    mask: Option<&[f32]>,
+) {
+    let Placement { offset_x, offset_y, inset, feather, curve, rounded_corners } = *placement;
+    let (core_x0, core_x1) = (inset, overlay_width - inset);
+    let (core_y0, core_y1) = (inset, overlay_height - inset);
+
+    let axis_weight = |d: usize| 1.0 - d as f32 / feather as f32;
+
+    base.par_chunks_exact_mut(base_width)
+        .enumerate()
+        .for_each(|(y_a, row)| {
+            let y_b = y_a as isize - offset_y;
+            if y_b < 0 || y_b >= overlay_height as isize {
+                return;
+            }
+            let y_b = y_b as usize;
+            let dy = core_distance(y_b, core_y0, core_y1);
+            if dy > feather {
+                return;
+            }
+
+            let x_end = (offset_x + overlay_width as isize).clamp(0, base_width as isize) as usize;
+            let x_start = (offset_x.max(0) as usize).min(x_end);
+
+            for (value, x_a) in row[x_start..x_end].iter_mut().zip(x_start..) {
+                let x_b = (x_a as isize - offset_x) as usize;
+                let dx = core_distance(x_b, core_x0, core_x1);
+                if dx > feather {
+                    continue;
+                }
+
+                let t = if dx == 0 && dy == 0 {
+                    1.0
+                } else if rounded_corners {
+                    let d = ((dx * dx + dy * dy) as f32).sqrt();
+                    (1.0 - d / feather as f32).max(0.0)
+                } else {
+                    axis_weight(dx).min(axis_weight(dy))
+                };
+
+                let index_b = y_b * overlay_width + x_b;
+                let mut weight = curve.apply(t);
+                if let Some(mask) = mask {
+                    weight *= mask[index_b].clamp(0.0, 1.0);
+                }
+
+                *value = overlay[index_b] * weight + *value * (1.0 - weight);
+            }
+        });
+}
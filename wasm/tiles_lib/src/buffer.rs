@@ -0,0 +1,67 @@
+use wasm_bindgen::prelude::*;
+use js_sys::Float32Array;
+use crate::raster::checked_length;
+
+// Row-major f32 raster owned by wasm memory. Released on drop or by calling free() from JS.
+#[wasm_bindgen]
+pub struct HeightBuffer {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl HeightBuffer {
+    // Zero-initialized buffer
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: usize, height: usize) -> Result<HeightBuffer, JsError> {
+        let length = checked_length("HeightBuffer.new", width, height)?;
+        Ok(Self { width, height, data: vec![0.0; length] })
+    }
+
+    pub fn from_data(width: usize, height: usize, data: Vec<f32>) -> Result<HeightBuffer, JsError> {
+        let length = checked_length("HeightBuffer.from_data", width, height)?;
+        if data.len() != length {
+            return Err(JsError::new(&format!(
+                "HeightBuffer.from_data: Invalid data length {}, expected {}x{}", data.len(), width, height
+            )));
+        }
+        Ok(Self { width, height, data })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+
+    // Zero-copy view of the data. It is detached when the wasm memory grows or the buffer is freed,
+    // so copy it with slice() or set() before calling anything else.
+    pub fn view(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.data) }
+    }
+}
+
+impl HeightBuffer {
+    pub fn from_vec(width: usize, height: usize, data: Vec<f32>) -> Self {
+        debug_assert_eq!(data.len(), width * height);
+        Self { width, height, data }
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+}
@@ -1,283 +1,545 @@
+mod blend;
+#[path = "../../shared/border.rs"]
+mod border;
+mod buffer;
+#[path = "../../shared/gaussian.rs"]
+mod gaussian;
+#[path = "../../shared/hash.rs"]
+mod hash;
+#[path = "../../shared/raster.rs"]
+mod raster;
+mod resample;
+mod subdivision;
+mod tiling;
+
 use wasm_bindgen::prelude::*;
-use rand::{rngs::SmallRng, Rng, SeedableRng};
+use blend::{FeatherCurve, Placement};
+use border::BorderMode;
+pub use buffer::HeightBuffer;
+use gaussian::gaussian_reach;
+use raster::raster_length;
+use resample::{axis_weights, downscale_weights, resample, AxisWeights, DownscaleFilter, ResampleFilter};
+use subdivision::{Displacement, Distribution};
+use tiling::TileLayout;
+
+fn scaled_length(length: usize, scale: f32) -> usize {
+    ((length as f64) * (scale as f64)).round() as usize
+}
 
-#[wasm_bindgen]
-pub fn allocate_memory(length: usize) -> *mut f32 {
-    let mut buffer = Vec::with_capacity(length);
-    unsafe { buffer.set_len(length) }
-    let ptr = buffer.as_mut_ptr();
-    std::mem::forget(buffer);
-    ptr
+// border: "replicate" | "reflect" | "wrap" | "constant". `border_value` is only used by "constant".
+fn border_mode(name: &str, border: &str, border_value: f32) -> Result<BorderMode, JsError> {
+    BorderMode::from_name(border, border_value)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid border mode or value", name)))
 }
 
+// [width, height] of the scale_up output. `width` and `height` are the size of the input buffer,
+// padding included, like the output size. Use it to allocate the full output before calling
+// scale_up_rows strip by strip.
 #[wasm_bindgen]
-pub fn free_memory(ptr: *mut f32, size: usize) {
-    unsafe {
-        let _ = Vec::from_raw_parts(ptr, size, size);
-    }
+pub fn scale_up_size(width: usize, height: usize, padding: usize, scale: f32) -> Result<Vec<u32>, JsError> {
+    let size = scale_size("scale_up_size", width, height, padding, scale)?;
+    Ok(vec![size.output_width as u32, size.output_height as u32])
 }
 
+// The input is a core surrounded by `padding` pixels on every side.
+// The output core is scaled by `scale` and keeps the same padding in output pixels.
 #[wasm_bindgen]
-pub fn scale_up_bicubic(input_ptr: *const f32, output_ptr: *mut f32) {
-    const ORIGINAL_SIZE: usize = 4096;
-    const PADDING: usize = 100;
-    const SCALED_PADDING: usize = 75;
-    const FULL_SIZE: usize = ORIGINAL_SIZE + 2 * PADDING;
-    const SCALE: usize = 4;
-    const NEW_SIZE: usize = ORIGINAL_SIZE * SCALE;
-    const OUTPUT_SIZE: usize = NEW_SIZE + 2 * PADDING;
-
-    // Mitchell-Netravali cubic filter parameters (b = 1/3, c = 1/3)
-    let b = 1.0 / 3.0;
-    let c = 1.0 / 3.0;
-    let coeff = cubic_bc_coefficient(b, c);
-
-    let input = unsafe { std::slice::from_raw_parts(input_ptr, FULL_SIZE * FULL_SIZE) };
-    let output = unsafe { std::slice::from_raw_parts_mut(output_ptr, OUTPUT_SIZE * OUTPUT_SIZE) };
-
-    for y in 0..OUTPUT_SIZE {
-        let pos_y = y as f32 / SCALE as f32;
-        let y0 = pos_y.floor() as usize + SCALED_PADDING;
-        let ty = pos_y - pos_y.floor();
-
-        for x in 0..OUTPUT_SIZE {
-            let pos_x = x as f32 / SCALE as f32;
-            let x0 = pos_x.floor() as usize + SCALED_PADDING;
-            let tx = pos_x - pos_x.floor();
-
-            let fx = [
-                cubic_func(1.0 + tx, &coeff),
-                cubic_func(tx, &coeff),
-                cubic_func(1.0 - tx, &coeff),
-                cubic_func(2.0 - tx, &coeff),
-            ];
-            
-            let fy = [
-                cubic_func(1.0 + ty, &coeff),
-                cubic_func(ty, &coeff),
-                cubic_func(1.0 - ty, &coeff),
-                cubic_func(2.0 - ty, &coeff),
-            ];
-
-            let tmp_vals = [
-                fy[0] * input[(y0 - 1) * FULL_SIZE + (x0 - 1)] + 
-                fy[1] * input[y0 * FULL_SIZE + (x0 - 1)] + 
-                fy[2] * input[(y0 + 1) * FULL_SIZE + (x0 - 1)] + 
-                fy[3] * input[(y0 + 2) * FULL_SIZE + (x0 - 1)],
-
-                fy[0] * input[(y0 - 1) * FULL_SIZE + x0] + 
-                fy[1] * input[y0 * FULL_SIZE + x0] + 
-                fy[2] * input[(y0 + 1) * FULL_SIZE + x0] + 
-                fy[3] * input[(y0 + 2) * FULL_SIZE + x0],
-
-                fy[0] * input[(y0 - 1) * FULL_SIZE + (x0 + 1)] + 
-                fy[1] * input[y0 * FULL_SIZE + (x0 + 1)] + 
-                fy[2] * input[(y0 + 1) * FULL_SIZE + (x0 + 1)] + 
-                fy[3] * input[(y0 + 2) * FULL_SIZE + (x0 + 1)],
-
-                fy[0] * input[(y0 - 1) * FULL_SIZE + (x0 + 2)] + 
-                fy[1] * input[y0 * FULL_SIZE + (x0 + 2)] + 
-                fy[2] * input[(y0 + 1) * FULL_SIZE + (x0 + 2)] + 
-                fy[3] * input[(y0 + 2) * FULL_SIZE + (x0 + 2)],
-            ];
-
-            output[y * OUTPUT_SIZE + x] =
-                fx[0] * tmp_vals[0] + fx[1] * tmp_vals[1] + 
-                fx[2] * tmp_vals[2] + fx[3] * tmp_vals[3];
-        }
-    }
+pub fn scale_up_bicubic(input: &HeightBuffer, padding: usize, scale: f32, monotone: bool) -> Result<HeightBuffer, JsError> {
+    scale_up(input, padding, scale, "bicubic", 0.0, 0.0, monotone, "replicate", 0.0)
 }
 
-fn cubic_bc_coefficient(b: f32, c: f32) -> [f32; 8] {
-    let p = 2.0 - 1.5 * b - c;
-    let q = -3.0 + 2.0 * b + c;
-    let r = 0.0;
-    let s = 1.0 - (1.0 / 3.0) * b;
-    let t = -(1.0 / 6.0) * b - c;
-    let u = b + 5.0 * c;
-    let v = -2.0 * b - 8.0 * c;
-    let w = (4.0 / 3.0) * b + 4.0 * c;
-    [p, q, r, s, t, u, v, w]
+struct ScaleSize {
+    input_width: usize,
+    input_height: usize,
+    output_width: usize,
+    output_height: usize,
+    scale_x: f64,
+    scale_y: f64,
 }
 
-fn cubic_func(x: f32, coeff: &[f32; 8]) -> f32 {
-    let ax = x.abs();
-    if ax < 1.0 {
-        ((coeff[0] * ax + coeff[1]) * ax + coeff[2]) * ax + coeff[3]
-    } else if ax < 2.0 {
-        ((coeff[4] * ax + coeff[5]) * ax + coeff[6]) * ax + coeff[7]
-    } else {
-        0.0
+// The input is a core surrounded by `padding` pixels on every side.
+fn scale_size(name: &str, input_width: usize, input_height: usize, padding: usize, scale: f32) -> Result<ScaleSize, JsError> {
+    if input_width <= 2 * padding || input_height <= 2 * padding {
+        return Err(JsError::new(&format!("{}: Input must be larger than the padding", name)));
+    }
+    if !(scale.is_finite() && scale > 0.0) {
+        return Err(JsError::new(&format!("{}: Scale must be a positive number", name)));
     }
-}
 
-fn add_padding(data: &[f32], padding: usize) -> Vec<f32> {
-    let side = (data.len() as f32).sqrt() as usize;
-    let mut padded_data = Vec::with_capacity((side + 2 * padding) * (side + 2 * padding));
-    
-    for _ in 0..padding {
-        padded_data.extend(std::iter::repeat(data[0]).take(padding));
-        padded_data.extend(&data[0..side]);
-        padded_data.extend(std::iter::repeat(data[side-1]).take(padding));
+    let width = input_width - 2 * padding;
+    let height = input_height - 2 * padding;
+    let new_width = scaled_length(width, scale);
+    let new_height = scaled_length(height, scale);
+    if new_width == 0 || new_height == 0 {
+        return Err(JsError::new(&format!("{}: Scaled size must be greater than 0", name)));
     }
-    
-    for r in 0..side {
-        padded_data.extend(std::iter::repeat(data[r * side]).take(padding));
-        padded_data.extend(&data[r * side..(r + 1) * side]);
-        padded_data.extend(std::iter::repeat(data[(r + 1) * side - 1]).take(padding));
+
+    let output_width = new_width + 2 * padding;
+    let output_height = new_height + 2 * padding;
+    if raster_length(output_width, output_height).is_none() {
+        return Err(JsError::new(&format!("{}: Output size is too large", name)));
     }
-    
-    for _ in 0..padding {
-        padded_data.extend(std::iter::repeat(data[(side - 1) * side]).take(padding));
-        padded_data.extend(&data[(side - 1) * side..side * side]);
-        padded_data.extend(std::iter::repeat(data[side * side - 1]).take(padding));
+
+    // Use the ratio of the rounded sizes so that the core edges line up exactly.
+    Ok(ScaleSize {
+        input_width,
+        input_height,
+        output_width,
+        output_height,
+        scale_x: new_width as f64 / width as f64,
+        scale_y: new_height as f64 / height as f64,
+    })
+}
+
+struct ScalePlan {
+    filter: ResampleFilter,
+    border: BorderMode,
+    size: ScaleSize,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn scale_plan(name: &str, input: &HeightBuffer, padding: usize, scale: f32, filter: &str, b: f32, c: f32, border: BorderMode) -> Result<ScalePlan, JsError> {
+    let filter = ResampleFilter::from_name(filter, b, c)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid filter", name)))?;
+    let size = scale_size(name, input.width(), input.height(), padding, scale)?;
+    Ok(ScalePlan { filter, border, size })
+}
+
+// filter: "nearest" | "bilinear" | "bicubic" | "catmull-rom" | "b-spline" | "cubic" | "lanczos2" | "lanczos3"
+// `b` and `c` are the parameters of "cubic" and are ignored by the other filters.
+// `monotone` clamps every output sample to its source neighbourhood to stop ringing at cliffs and coastlines.
+// `border` and `border_value` set how the filter reads pixels outside the input (see border_mode).
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn scale_up(
+    input: &HeightBuffer,
+    padding: usize,
+    scale: f32,
+    filter: &str,
+    b: f32,
+    c: f32,
+    monotone: bool,
+    border: &str,
+    border_value: f32,
+) -> Result<HeightBuffer, JsError> {
+    let border = border_mode("scale_up", border, border_value)?;
+    let plan = scale_plan("scale_up", input, padding, scale, filter, b, c, border)?;
+    Ok(scale_up_range(input, padding, &plan, 0, plan.size.output_height, monotone))
+}
+
+// Same as scale_up, but only computes output rows [y0, y1) of the full output (padding included).
+// scale_up_size with the same input size, padding and scale gives the full output size,
+// so a large map can be processed strip by strip.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn scale_up_rows(
+    input: &HeightBuffer,
+    padding: usize,
+    scale: f32,
+    filter: &str,
+    b: f32,
+    c: f32,
+    monotone: bool,
+    border: &str,
+    border_value: f32,
+    y0: usize,
+    y1: usize,
+) -> Result<HeightBuffer, JsError> {
+    let border = border_mode("scale_up_rows", border, border_value)?;
+    let plan = scale_plan("scale_up_rows", input, padding, scale, filter, b, c, border)?;
+    if y0 >= y1 || y1 > plan.size.output_height {
+        return Err(JsError::new(&format!(
+            "scale_up_rows: Invalid row range [{}, {}), output height is {}", y0, y1, plan.size.output_height
+        )));
     }
-    
-    padded_data
+    Ok(scale_up_range(input, padding, &plan, y0, y1, monotone))
 }
 
-fn blur_data(data: &[f32], k: &[f32]) -> Vec<f32> {
-    let width = (data.len() as f32).sqrt() as usize;
-    let height = width;
-    
-    let sum = k.iter().sum::<f32>();
-    let k = [
-        k[0] / sum,
-        k[1] / sum,
-        k[2] / sum,
-        (k[1] + k[0]) / sum  // k_edge
-    ];
-    
-    let mut result = vec![0.0; data.len()];
-    
-    // Horizontal pass
-    for y in 0..height {
-        let row_offset = y * width;
-        // Left edge
-        result[row_offset] = data[row_offset] * k[3] + data[row_offset + 1] * k[2];
-        // Middle
-        for x in 1..width-1 {
-            let pos = row_offset + x;
-            result[pos] = data[pos - 1] * k[0] + data[pos] * k[1] + data[pos + 1] * k[2];
-        }
-        // Right edge
-        let x = width - 1;
-        result[row_offset + x] = data[row_offset + x - 1] * k[0] + data[row_offset + x] * k[3];
+fn scale_up_range(input: &HeightBuffer, padding: usize, plan: &ScalePlan, y0: usize, y1: usize, monotone: bool) -> HeightBuffer {
+    let size = &plan.size;
+    let wx = axis_weights(0..size.output_width, size.input_width, padding, size.scale_x, &plan.filter, &plan.border);
+    let wy = axis_weights(y0..y1, size.input_height, padding, size.scale_y, &plan.filter, &plan.border);
+
+    let mut output = vec![0.0; size.output_width * (y1 - y0)];
+    resample(input.data(), size.input_width, &mut output, size.output_width, &wx, &wy, &plan.border, monotone);
+
+    HeightBuffer::from_vec(size.output_width, y1 - y0, output)
+}
+
+struct DownscalePlan {
+    filter: DownscaleFilter,
+    border: BorderMode,
+    input_width: usize,
+    input_height: usize,
+    output_width: usize,
+    output_height: usize,
+}
+
+impl DownscalePlan {
+    // Use the ratio of the rounded sizes so that the edges line up exactly.
+    fn weights_x(&self) -> AxisWeights {
+        let factor = self.input_width as f64 / self.output_width as f64;
+        downscale_weights(0..self.output_width, self.input_width, factor, &self.filter, &self.border)
     }
-    
-    // Vertical pass (in-place modification)
-    let temp = result.clone();
-    
-    // Top edge
-    for x in 0..width {
-        result[x] = temp[x] * k[3] + temp[width + x] * k[2];
+
+    fn weights_y(&self, y0: usize, y1: usize) -> AxisWeights {
+        let factor = self.input_height as f64 / self.output_height as f64;
+        downscale_weights(y0..y1, self.input_height, factor, &self.filter, &self.border)
     }
 
-    // Middle rows
-    for y in 1..height-1 {
-        let row_offset = y * width;
-        for x in 0..width {
-            let pos = row_offset + x;
-            result[pos] = temp[pos - width] * k[0] + temp[pos] * k[1] + temp[pos + width] * k[2];
+    fn validate_rows(&self, name: &str, y0: usize, y1: usize) -> Result<(), JsError> {
+        if y0 >= y1 || y1 > self.output_height {
+            return Err(JsError::new(&format!(
+                "{}: Invalid row range [{}, {}), output height is {}", name, y0, y1, self.output_height
+            )));
         }
+        Ok(())
+    }
+}
+
+fn downscale_plan(name: &str, width: usize, height: usize, factor: f32, filter: &str, border: &str, border_value: f32) -> Result<DownscalePlan, JsError> {
+    let filter = DownscaleFilter::from_name(filter)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid filter", name)))?;
+    let border = border_mode(name, border, border_value)?;
+    if !(factor.is_finite() && factor >= 1.0) {
+        return Err(JsError::new(&format!("{}: Factor must be a number greater than or equal to 1", name)));
     }
-    
-    // Bottom edge
-    let last_row = (height - 1) * width;
-    for x in 0..width {
-        result[last_row + x] = temp[last_row - width + x] * k[0] + temp[last_row + x] * k[3];
+    if width == 0 || height == 0 {
+        return Err(JsError::new(&format!("{}: Width and height must be greater than 0", name)));
     }
-    
-    result
+
+    Ok(DownscalePlan {
+        filter,
+        border,
+        input_width: width,
+        input_height: height,
+        output_width: scaled_length(width, 1.0 / factor).max(1),
+        output_height: scaled_length(height, 1.0 / factor).max(1),
+    })
+}
+
+// Shrinks the input by `factor` (>= 1) on both axes with a prefilter to avoid aliasing.
+// filter: "box" | "area" | "lanczos2" | "lanczos3"
+#[wasm_bindgen]
+pub fn scale_down(input: &HeightBuffer, factor: f32, filter: &str, border: &str, border_value: f32) -> Result<HeightBuffer, JsError> {
+    let plan = downscale_plan("scale_down", input.width(), input.height(), factor, filter, border, border_value)?;
+    let wx = plan.weights_x();
+    let wy = plan.weights_y(0, plan.output_height);
+
+    let mut output = vec![0.0; plan.output_width * plan.output_height];
+    resample(input.data(), plan.input_width, &mut output, plan.output_width, &wx, &wy, &plan.border, false);
+
+    Ok(HeightBuffer::from_vec(plan.output_width, plan.output_height, output))
+}
+
+// [width, height] of the scale_down output of a width x height input
+#[wasm_bindgen]
+pub fn scale_down_size(width: usize, height: usize, factor: f32) -> Result<Vec<u32>, JsError> {
+    let plan = downscale_plan("scale_down_size", width, height, factor, "area", "replicate", 0.0)?;
+    Ok(vec![plan.output_width as u32, plan.output_height as u32])
 }
 
-fn subdivide(elevation: &[f32], blured_elevation: &[f32], padding: usize) -> Vec<f32> {
-    let core_size = (elevation.len() as f64).sqrt() as usize;
-    let blured_elev_size = (blured_elevation.len() as f64).sqrt() as usize;
+// [first, last + 1) of the input rows that scale_down_rows needs for output rows [y0, y1)
+// of a width x height input. The other parameters are the same as scale_down.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn scale_down_source_rows(
+    width: usize,
+    height: usize,
+    factor: f32,
+    filter: &str,
+    border: &str,
+    border_value: f32,
+    y0: usize,
+    y1: usize,
+) -> Result<Vec<u32>, JsError> {
+    let plan = downscale_plan("scale_down_source_rows", width, height, factor, filter, border, border_value)?;
+    plan.validate_rows("scale_down_source_rows", y0, y1)?;
+    let rows = plan.weights_y(y0, y1).source_range();
+    Ok(vec![rows.start as u32, rows.end as u32])
+}
 
-    if core_size != blured_elev_size - padding * 2 {
-        panic!("Invalid data size");
+// Same as scale_down, but only computes output rows [y0, y1) of a source that is `height` rows tall.
+// `input` holds source rows [source_y0, source_y0 + input.height), which must cover the rows
+// from scale_down_source_rows, so a large map can be shrunk strip by strip.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn scale_down_rows(
+    input: &HeightBuffer,
+    height: usize,
+    source_y0: usize,
+    factor: f32,
+    filter: &str,
+    border: &str,
+    border_value: f32,
+    y0: usize,
+    y1: usize,
+) -> Result<HeightBuffer, JsError> {
+    let plan = downscale_plan("scale_down_rows", input.width(), height, factor, filter, border, border_value)?;
+    plan.validate_rows("scale_down_rows", y0, y1)?;
+
+    let wx = plan.weights_x();
+    let mut wy = plan.weights_y(y0, y1);
+    let rows = wy.source_range();
+    if rows.start < source_y0 || rows.end > source_y0 + input.height() {
+        return Err(JsError::new(&format!(
+            "scale_down_rows: Input rows [{}, {}) do not cover the source rows [{}, {})",
+            source_y0, source_y0 + input.height(), rows.start, rows.end
+        )));
     }
+    wy.shift(source_y0);
 
-    let vertex_size = core_size + 1;
-    let result_size = core_size * 2;
-    let mut elevation_vertex = vec![0.0; vertex_size * vertex_size];
-    let mut result = vec![0.0; result_size * result_size];
+    let mut output = vec![0.0; plan.output_width * (y1 - y0)];
+    resample(input.data(), plan.input_width, &mut output, plan.output_width, &wx, &wy, &plan.border, false);
 
-    let mut rng = SmallRng::from_entropy();
+    Ok(HeightBuffer::from_vec(plan.output_width, y1 - y0, output))
+}
 
-    let mut window_buffer = [[0.0; 4]; 4];
+fn blend_placement(
+    name: &str,
+    overlay: &HeightBuffer,
+    (offset_x, offset_y): (i32, i32),
+    inset: usize,
+    feather: usize,
+    curve: &str,
+    rounded_corners: bool,
+) -> Result<Placement, JsError> {
+    let curve = FeatherCurve::from_name(curve)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid curve", name)))?;
+    if 2 * inset >= overlay.width() || 2 * inset >= overlay.height() {
+        return Err(JsError::new(&format!("{}: Overlay must be larger than the inset", name)));
+    }
+    if feather > inset {
+        return Err(JsError::new(&format!("{}: Feather must not be larger than the inset", name)));
+    }
 
-    let calculate_vertex_elevation = |window: &[[f32; 4]; 4], rng: &mut SmallRng| -> f32 {
-        let s0 = (window[1][1] - window[0][0]) / 2.0 + window[1][1];
-        let s1 = (window[1][2] - window[0][3]) / 2.0 + window[1][2];
-        let s2 = (window[2][1] - window[3][0]) / 2.0 + window[2][1];
-        let s3 = (window[2][2] - window[3][3]) / 2.0 + window[2][2];
+    Ok(Placement {
+        offset_x: offset_x as isize,
+        offset_y: offset_y as isize,
+        inset,
+        feather,
+        curve,
+        rounded_corners,
+    })
+}
 
-        let reference = (s0 + s1 + s2 + s3) / 4.0;
-        let range = (s0.max(s1).max(s2).max(s3) - s0.min(s1).min(s2).min(s3)) * 0.1259;
-        reference + (rng.gen_range(-0.5, 0.5) * range)
-    };
+// Embeds `overlay` into `base` in place, with its top-left corner at (offset_x, offset_y) of the base.
+// The overlay minus `inset` pixels on each side replaces the base, and the `feather` pixels
+// outside of it fade into the base. Parts outside the base are ignored.
+// curve: "linear" | "smoothstep" | "cosine"
+// `rounded_corners` uses the distance to the core in the corners instead of the smaller axis weight.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn blend_maps(
+    base: &mut HeightBuffer,
+    overlay: &HeightBuffer,
+    offset_x: i32,
+    offset_y: i32,
+    inset: usize,
+    feather: usize,
+    curve: &str,
+    rounded_corners: bool,
+) -> Result<(), JsError> {
+    let placement = blend_placement("blend_maps", overlay, (offset_x, offset_y), inset, feather, curve, rounded_corners)?;
+    let base_width = base.width();
+    blend::blend_maps(base.data_mut(), base_width, overlay.data(), overlay.width(), overlay.height(), &placement, None);
+    Ok(())
+}
 
-    for y in 0..vertex_size {
-        let row_offset0 = y * blured_elev_size;
-        let row_offset1 = (y + 1) * blured_elev_size;
-        let row_offset2 = (y + 2) * blured_elev_size;
-        let row_offset3 = (y + 3) * blured_elev_size;
+// Same as blend_maps, with `mask` (overlay size, 0 to 1) scaling the overlay weight per pixel.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn blend_maps_with_mask(
+    base: &mut HeightBuffer,
+    overlay: &HeightBuffer,
+    mask: &HeightBuffer,
+    offset_x: i32,
+    offset_y: i32,
+    inset: usize,
+    feather: usize,
+    curve: &str,
+    rounded_corners: bool,
+) -> Result<(), JsError> {
+    let placement = blend_placement("blend_maps_with_mask", overlay, (offset_x, offset_y), inset, feather, curve, rounded_corners)?;
+    if mask.width() != overlay.width() || mask.height() != overlay.height() {
+        return Err(JsError::new("blend_maps_with_mask: Mask size must match the overlay size"));
+    }
+    let base_width = base.width();
+    blend::blend_maps(base.data_mut(), base_width, overlay.data(), overlay.width(), overlay.height(), &placement, Some(mask.data()));
+    Ok(())
+}
 
-        for x in 0..vertex_size {
-            window_buffer[0].copy_from_slice(&blured_elevation[row_offset0 + x..row_offset0 + x + 4]);
-            window_buffer[1].copy_from_slice(&blured_elevation[row_offset1 + x..row_offset1 + x + 4]);
-            window_buffer[2].copy_from_slice(&blured_elevation[row_offset2 + x..row_offset2 + x + 4]);
-            window_buffer[3].copy_from_slice(&blured_elevation[row_offset3 + x..row_offset3 + x + 4]);
+fn tile_layout(name: &str, width: usize, height: usize, divisions_x: usize, divisions_y: usize, halo: usize) -> Result<TileLayout, JsError> {
+    if divisions_x == 0 || divisions_y == 0 {
+        return Err(JsError::new(&format!("{}: Divisions must be greater than 0", name)));
+    }
+    if divisions_x > width || divisions_y > height {
+        return Err(JsError::new(&format!("{}: Too many divisions for the map size", name)));
+    }
 
-            elevation_vertex[y * vertex_size + x] = calculate_vertex_elevation(&window_buffer, &mut rng);
-        }
+    let layout = TileLayout { width, height, divisions_x, divisions_y, halo };
+    if halo > layout.min_cell_size() {
+        return Err(JsError::new(&format!("{}: Halo must not be larger than a tile cell", name)));
     }
+    Ok(layout)
+}
+
+// Splits the map into divisions_x x divisions_y tiles in row-major order.
+// Each tile is its cell plus `halo` pixels of the neighbouring cells, clamped at the map edges.
+#[wasm_bindgen]
+pub fn split_tiles(input: &HeightBuffer, divisions_x: usize, divisions_y: usize, halo: usize) -> Result<Vec<HeightBuffer>, JsError> {
+    let layout = tile_layout("split_tiles", input.width(), input.height(), divisions_x, divisions_y, halo)?;
+
+    let tiles = layout.split(input.data())
+        .into_iter()
+        .enumerate()
+        .map(|(i, tile)| {
+            let rect = layout.tile_rect(i % divisions_x, i / divisions_x);
+            HeightBuffer::from_vec(rect.width(), rect.height(), tile)
+        })
+        .collect();
+
+    Ok(tiles)
+}
 
-    for y in 0..core_size {
-        let core_offset = y * core_size;
-        let vertex_offset0 = y * vertex_size;
-        let vertex_offset1 = (y + 1) * vertex_size;
-        let result_offset0 = y * 2 * result_size;
-        let result_offset1 = (y * 2 + 1) * result_size;
-
-        for x in 0..core_size {
-            result[result_offset0 + x * 2] = (elevation_vertex[vertex_offset0 + x] + elevation[core_offset + x]) / 2.0;
-            result[result_offset0 + x * 2 + 1] = (elevation_vertex[vertex_offset0 + x + 1] + elevation[core_offset + x]) / 2.0;
-            result[result_offset1 + x * 2] = (elevation_vertex[vertex_offset1 + x] + elevation[core_offset + x]) / 2.0;
-            result[result_offset1 + x * 2 + 1] = (elevation_vertex[vertex_offset1 + x + 1] + elevation[core_offset + x]) / 2.0;
+// Reassembles tiles from split_tiles with the same parameters.
+// The overlapping halos are cross-faded, so tile-local effects do not leave hard seams.
+#[wasm_bindgen]
+pub fn merge_tiles(tiles: Vec<HeightBuffer>, width: usize, height: usize, divisions_x: usize, divisions_y: usize, halo: usize) -> Result<HeightBuffer, JsError> {
+    let layout = tile_layout("merge_tiles", width, height, divisions_x, divisions_y, halo)?;
+    if tiles.len() != divisions_x * divisions_y {
+        return Err(JsError::new(&format!(
+            "merge_tiles: Invalid tile count {}, expected {}", tiles.len(), divisions_x * divisions_y
+        )));
+    }
+    for (i, tile) in tiles.iter().enumerate() {
+        let rect = layout.tile_rect(i % divisions_x, i / divisions_x);
+        if tile.width() != rect.width() || tile.height() != rect.height() {
+            return Err(JsError::new(&format!(
+                "merge_tiles: Invalid size of tile {}, expected {}x{}", i, rect.width(), rect.height()
+            )));
         }
     }
 
-    result
+    let tile_data: Vec<&[f32]> = tiles.iter().map(|tile| tile.data()).collect();
+    Ok(HeightBuffer::from_vec(width, height, layout.merge(&tile_data)))
+}
+
+// Minimum halo for split_tiles/merge_tiles so that a Gaussian blur or unsharp mask of `radius`
+// (as in effects_lib) does not reach the cross-faded part of the halo from the tile edge.
+#[wasm_bindgen]
+pub fn min_tile_halo(radius: f32) -> Result<usize, JsError> {
+    if !(radius.is_finite() && radius > 0.0) {
+        return Err(JsError::new("min_tile_halo: Radius must be a positive number"));
+    }
+    // Only the inner half of the halo is cross-faded.
+    Ok(2 * gaussian_reach(radius))
+}
+
+fn displacement_params(name: &str, kernel: &[f32], roughness: f32, distribution: &str, decay: f32) -> Result<Displacement, JsError> {
+    let distribution = Distribution::from_name(distribution)
+        .ok_or_else(|| JsError::new(&format!("{}: Invalid distribution", name)))?;
+
+    subdivision::normalize_kernel(kernel)
+        .map_err(|err| JsError::new(&format!("{}: {}", name, err)))?;
+    if !(roughness.is_finite() && roughness >= 0.0) {
+        return Err(JsError::new(&format!("{}: Roughness must be a non-negative number", name)));
+    }
+    if !(decay.is_finite() && decay >= 0.0) {
+        return Err(JsError::new(&format!("{}: Decay must be a non-negative number", name)));
+    }
+
+    Ok(Displacement { roughness, distribution, decay })
 }
 
+// `kernel` is an odd-length separable blur kernel, normalized to sum to 1.
+// distribution: "uniform" | "gaussian" | "none"
+// `roughness` scales the displacement range (0.1259 by default) and is multiplied by `decay` at every level.
+// `border` and `border_value` extend the map before every blur (see border_mode).
+// The result is (width << count) x (height << count).
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn subdivide_by_gradient(
-    elevation_ptr: *mut f32,
-    elevation_len: usize,
+    elevation: &HeightBuffer,
     kernel: &[f32],
     count: usize,
-) -> *mut f32 {
-    let elevation = unsafe { std::slice::from_raw_parts(elevation_ptr, elevation_len) };
-    let padding = 2;
+    seed: u32,
+    offset_x: i32,
+    offset_y: i32,
+    roughness: f32,
+    distribution: &str,
+    decay: f32,
+    border: &str,
+    border_value: f32,
+) -> Result<HeightBuffer, JsError> {
+    let displacement = displacement_params("subdivide_by_gradient", kernel, roughness, distribution, decay)?;
+    let border = border_mode("subdivide_by_gradient", border, border_value)?;
+
+    let width = elevation.width();
+    let height = elevation.height();
+    let (result_width, result_height) = subdivision::subdivided_size(width, height, count)
+        .filter(|&(w, h)| raster_length(w, h).is_some())
+        .ok_or_else(|| JsError::new("subdivide_by_gradient: Count is too large for the elevation size"))?;
+
+    let result = subdivision::subdivide_by_gradient(elevation.data(), width, height, kernel, count, &displacement, &border, seed, offset_x as i64, offset_y as i64)
+        .map_err(|err| JsError::new(&format!("subdivide_by_gradient: {}", err)))?;
+
+    Ok(HeightBuffer::from_vec(result_width, result_height, result))
+}
+
+// Subdivides up to the smallest power of two that covers target_width x target_height,
+// then shrinks the result to exactly that size with `filter` ("box" | "area" | "lanczos2" | "lanczos3").
+// The other parameters are the same as subdivide_by_gradient.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn subdivide_to_size(
+    elevation: &HeightBuffer,
+    kernel: &[f32],
+    target_width: usize,
+    target_height: usize,
+    seed: u32,
+    offset_x: i32,
+    offset_y: i32,
+    roughness: f32,
+    distribution: &str,
+    decay: f32,
+    border: &str,
+    border_value: f32,
+    filter: &str,
+) -> Result<HeightBuffer, JsError> {
+    let displacement = displacement_params("subdivide_to_size", kernel, roughness, distribution, decay)?;
+    let border = border_mode("subdivide_to_size", border, border_value)?;
+    let filter = DownscaleFilter::from_name(filter)
+        .ok_or_else(|| JsError::new("subdivide_to_size: Invalid filter"))?;
+    if target_width == 0 || target_height == 0 {
+        return Err(JsError::new("subdivide_to_size: Target width and height must be greater than 0"));
+    }
 
-    fn recursive_subdivide(elev: &[f32], kernel: &[f32], index: usize, padding: usize) -> Vec<f32> {
-        if index == 0 {
-            return elev.to_vec();
+    let width = elevation.width();
+    let height = elevation.height();
+    let mut count = 0;
+    let (subdivided_width, subdivided_height) = loop {
+        let size = subdivision::subdivided_size(width, height, count)
+            .filter(|&(w, h)| raster_length(w, h).is_some())
+            .ok_or_else(|| JsError::new("subdivide_to_size: Target size is too large for the elevation size"))?;
+        if size.0 >= target_width && size.1 >= target_height {
+            break size;
         }
-        let padded_elevation = add_padding(elev, padding);
-        let blured_elevation = blur_data(&padded_elevation, kernel);
-        let subdivided = subdivide(elev, &blured_elevation, padding);
-        recursive_subdivide(&subdivided, kernel, index - 1, padding)
+        count += 1;
+    };
+
+    let subdivided = subdivision::subdivide_by_gradient(elevation.data(), width, height, kernel, count, &displacement, &border, seed, offset_x as i64, offset_y as i64)
+        .map_err(|err| JsError::new(&format!("subdivide_to_size: {}", err)))?;
+
+    if (subdivided_width, subdivided_height) == (target_width, target_height) {
+        return Ok(HeightBuffer::from_vec(target_width, target_height, subdivided));
     }
 
-    let result = recursive_subdivide(elevation, kernel, count, padding);
+    let wx = downscale_weights(0..target_width, subdivided_width, subdivided_width as f64 / target_width as f64, &filter, &border);
+    let wy = downscale_weights(0..target_height, subdivided_height, subdivided_height as f64 / target_height as f64, &filter, &border);
 
-    let result_ptr = allocate_memory(result.len());
-    let result_slice = unsafe { std::slice::from_raw_parts_mut(result_ptr, result.len()) };
-    result_slice.copy_from_slice(&result);
+    let mut output = vec![0.0; target_width * target_height];
+    resample(&subdivided, subdivided_width, &mut output, target_width, &wx, &wy, &border, false);
 
-    result_ptr
+    Ok(HeightBuffer::from_vec(target_width, target_height, output))
 }
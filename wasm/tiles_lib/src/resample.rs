@@ -0,0 +1,297 @@
+use std::ops::Range;
+use rayon::prelude::*;
+use std::f32::consts::PI;
+use crate::border::BorderMode;
+
+#[derive(Clone, Copy)]
+pub enum ResampleFilter {
+    Nearest,
+    Bilinear,
+    // Piecewise cubic coefficients from `cubic_bc_coefficient`
+    Cubic([f32; 8]),
+    // Number of lobes
+    Lanczos(usize),
+}
+
+impl ResampleFilter {
+    // `b` and `c` are only used by "cubic".
+    pub fn from_name(name: &str, b: f32, c: f32) -> Option<Self> {
+        match name {
+            "nearest" => Some(Self::Nearest),
+            "bilinear" => Some(Self::Bilinear),
+            // Mitchell-Netravali (b = 1/3, c = 1/3)
+            "bicubic" | "mitchell" => Some(Self::cubic(1.0 / 3.0, 1.0 / 3.0)),
+            "catmull-rom" => Some(Self::cubic(0.0, 0.5)),
+            "b-spline" => Some(Self::cubic(1.0, 0.0)),
+            "cubic" => Some(Self::cubic(b, c)),
+            "lanczos2" => Some(Self::Lanczos(2)),
+            "lanczos3" => Some(Self::Lanczos(3)),
+            _ => None,
+        }
+    }
+
+    pub fn cubic(b: f32, c: f32) -> Self {
+        Self::Cubic(cubic_bc_coefficient(b, c))
+    }
+
+    // Half width of the filter support in source pixels
+    pub fn radius(&self) -> usize {
+        match self {
+            Self::Nearest | Self::Bilinear => 1,
+            Self::Cubic(_) => 2,
+            Self::Lanczos(a) => *a,
+        }
+    }
+
+    pub fn weight(&self, x: f32) -> f32 {
+        match self {
+            Self::Nearest => if (-0.5..0.5).contains(&x) { 1.0 } else { 0.0 },
+            Self::Bilinear => (1.0 - x.abs()).max(0.0),
+            Self::Cubic(coeff) => cubic_func(x, coeff),
+            Self::Lanczos(a) => lanczos_func(x, *a as f32),
+        }
+    }
+}
+
+fn cubic_bc_coefficient(b: f32, c: f32) -> [f32; 8] {
+    let p = 2.0 - 1.5 * b - c;
+    let q = -3.0 + 2.0 * b + c;
+    let r = 0.0;
+    let s = 1.0 - (1.0 / 3.0) * b;
+    let t = -(1.0 / 6.0) * b - c;
+    let u = b + 5.0 * c;
+    let v = -2.0 * b - 8.0 * c;
+    let w = (4.0 / 3.0) * b + 4.0 * c;
+    [p, q, r, s, t, u, v, w]
+}
+
+fn cubic_func(x: f32, coeff: &[f32; 8]) -> f32 {
+    let ax = x.abs();
+    if ax < 1.0 {
+        ((coeff[0] * ax + coeff[1]) * ax + coeff[2]) * ax + coeff[3]
+    } else if ax < 2.0 {
+        ((coeff[4] * ax + coeff[5]) * ax + coeff[6]) * ax + coeff[7]
+    } else {
+        0.0
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let px = PI * x;
+        px.sin() / px
+    }
+}
+
+fn lanczos_func(x: f32, a: f32) -> f32 {
+    if x.abs() < a {
+        sinc(x) * sinc(x / a)
+    } else {
+        0.0
+    }
+}
+
+// Source indices and normalized weights of every output position along one axis.
+// Each output position owns `taps` consecutive entries.
+// `outside` is the total weight of the taps that read the constant border value, one entry per output position.
+pub struct AxisWeights {
+    pub taps: usize,
+    pub index: Vec<usize>,
+    pub weight: Vec<f32>,
+    pub outside: Vec<f32>,
+}
+
+impl AxisWeights {
+    fn with_capacity(output_len: usize, taps: usize) -> Self {
+        Self {
+            taps,
+            index: Vec::with_capacity(output_len * taps),
+            weight: Vec::with_capacity(output_len * taps),
+            outside: Vec::with_capacity(output_len),
+        }
+    }
+
+    // Adds the weights of one output position. `taps` yields (source index, weight) pairs.
+    fn push(&mut self, taps: impl Iterator<Item = (isize, f32)>, input_len: usize, border: &BorderMode) {
+        let start = self.weight.len();
+        let mut outside = 0.0;
+        for (i, w) in taps {
+            match border.index(i, input_len) {
+                Some(i) => {
+                    self.index.push(i);
+                    self.weight.push(w);
+                }
+                None => {
+                    // Keep the index valid. The weight moves to `outside`.
+                    self.index.push(i.clamp(0, input_len as isize - 1) as usize);
+                    self.weight.push(0.0);
+                    outside += w;
+                }
+            }
+        }
+
+        let sum = self.weight[start..].iter().sum::<f32>() + outside;
+        if sum != 0.0 {
+            self.weight[start..].iter_mut().for_each(|w| *w /= sum);
+            outside /= sum;
+        }
+        self.outside.push(outside);
+    }
+
+    // [first, last + 1) of the source indices read by these weights
+    pub fn source_range(&self) -> Range<usize> {
+        let first = self.index.iter().copied().min().unwrap_or(0);
+        let last = self.index.iter().copied().max().unwrap_or(0);
+        first..last + 1
+    }
+
+    // Shifts every source index by -`start`, for an input that begins at `start`
+    pub fn shift(&mut self, start: usize) {
+        self.index.iter_mut().for_each(|i| *i -= start);
+    }
+}
+
+// Output position `o` samples the source at (o - padding) / scale + padding,
+// so the padding keeps its width in pixels on both sides.
+// Source indices outside [0, input_len) are read according to `border`.
+// Only the positions in `output_range` are computed, so a strip of the output can be resampled on its own.
+pub fn axis_weights(output_range: Range<usize>, input_len: usize, padding: usize, scale: f64, filter: &ResampleFilter, border: &BorderMode) -> AxisWeights {
+    let radius = filter.radius() as isize;
+    let taps = 2 * radius as usize;
+    let mut weights = AxisWeights::with_capacity(output_range.len(), taps);
+
+    for o in output_range {
+        let pos = (o as f64 - padding as f64) / scale + padding as f64;
+        let p0 = pos.floor();
+        let t = (pos - p0) as f32;
+        let p0 = p0 as isize;
+
+        let taps = (1 - radius..=radius).map(|offset| (p0 + offset, filter.weight(t - offset as f32)));
+        weights.push(taps, input_len, border);
+    }
+
+    weights
+}
+
+#[derive(Clone, Copy)]
+pub enum DownscaleFilter {
+    // Mean of the source pixels whose centers fall in the footprint of the output pixel
+    Box,
+    // Mean weighted by the overlap of each source pixel with the footprint
+    Area,
+    // Lanczos stretched by the scale factor, with the number of lobes
+    Lanczos(usize),
+}
+
+impl DownscaleFilter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(Self::Box),
+            "area" => Some(Self::Area),
+            "lanczos2" => Some(Self::Lanczos(2)),
+            "lanczos3" => Some(Self::Lanczos(3)),
+            _ => None,
+        }
+    }
+}
+
+// Output position `o` covers the source interval [o * factor, (o + 1) * factor), where `factor` >= 1.
+// Source indices outside [0, input_len) are read according to `border`.
+// Only the positions in `output_range` are computed, like axis_weights.
+pub fn downscale_weights(output_range: Range<usize>, input_len: usize, factor: f64, filter: &DownscaleFilter, border: &BorderMode) -> AxisWeights {
+    let reach = match filter {
+        DownscaleFilter::Box | DownscaleFilter::Area => 0.0,
+        DownscaleFilter::Lanczos(a) => *a as f64 * factor - factor / 2.0,
+    };
+    let taps = (factor + 2.0 * reach).ceil() as usize + 2;
+    let mut weights = AxisWeights::with_capacity(output_range.len(), taps);
+
+    for o in output_range {
+        let start = o as f64 * factor;
+        let end = start + factor;
+        let center = start + factor / 2.0;
+        let first = (start - reach).floor() as isize;
+
+        let taps = (first..first + taps as isize).map(|i| {
+            let w = match filter {
+                DownscaleFilter::Box => {
+                    let c = i as f64 + 0.5;
+                    if c >= start && c < end { 1.0 } else { 0.0 }
+                }
+                DownscaleFilter::Area => {
+                    (end.min(i as f64 + 1.0) - start.max(i as f64)).max(0.0) as f32
+                }
+                DownscaleFilter::Lanczos(a) => {
+                    let x = ((i as f64 + 0.5 - center) / factor) as f32;
+                    lanczos_func(x, *a as f32)
+                }
+            };
+            (i, w)
+        });
+        weights.push(taps, input_len, border);
+    }
+
+    weights
+}
+
+// Separable resampling. Each output row first combines its source rows (vertical pass),
+// then combines the columns of that row (horizontal pass). Rows are processed in parallel.
+// With `monotone`, every output sample is clamped to the min/max of the source pixels
+// under the filter window, which removes the overshoot of cubic and Lanczos filters at steps.
+// `border` must be the mode the weights were built with.
+#[allow(clippy::too_many_arguments)]
+pub fn resample(input: &[f32], input_width: usize, output: &mut [f32], output_width: usize, wx: &AxisWeights, wy: &AxisWeights, border: &BorderMode, monotone: bool) {
+    let border_value = border.constant();
+    let scratch = || {
+        let len = if monotone { input_width } else { 0 };
+        (vec![0.0; input_width], vec![0.0; len], vec![0.0; len])
+    };
+
+    output.par_chunks_exact_mut(output_width)
+        .enumerate()
+        .for_each_init(scratch, |(tmp_row, min_row, max_row), (y, row)| {
+            let rows = &wy.index[y * wy.taps..(y + 1) * wy.taps];
+            let fy = &wy.weight[y * wy.taps..(y + 1) * wy.taps];
+            let outside_y = wy.outside[y];
+
+            // Vertical pass
+            for (col, tmp) in tmp_row.iter_mut().enumerate() {
+                *tmp = rows.iter()
+                    .zip(fy)
+                    .map(|(&r, &f)| f * input[r * input_width + col])
+                    .sum::<f32>() + outside_y * border_value;
+            }
+
+            if monotone {
+                // The border value is part of the window when some of its taps have a weight
+                let init = if outside_y != 0.0 { (border_value, border_value) } else { (f32::INFINITY, f32::NEG_INFINITY) };
+                for col in 0..input_width {
+                    let (min, max) = rows.iter()
+                        .map(|&r| input[r * input_width + col])
+                        .fold(init, |(min, max), v| (min.min(v), max.max(v)));
+                    min_row[col] = min;
+                    max_row[col] = max;
+                }
+            }
+
+            // Horizontal pass
+            for (x, value) in row.iter_mut().enumerate() {
+                let cols = &wx.index[x * wx.taps..(x + 1) * wx.taps];
+                let fx = &wx.weight[x * wx.taps..(x + 1) * wx.taps];
+
+                let outside_x = wx.outside[x];
+
+                *value = cols.iter().zip(fx).map(|(&col, f)| f * tmp_row[col]).sum::<f32>() + outside_x * border_value;
+
+                if monotone {
+                    let init = if outside_x != 0.0 { border_value } else { f32::INFINITY };
+                    let min = cols.iter().map(|&col| min_row[col]).fold(init, f32::min);
+                    let init = if outside_x != 0.0 { border_value } else { f32::NEG_INFINITY };
+                    let max = cols.iter().map(|&col| max_row[col]).fold(init, f32::max);
+                    *value = value.clamp(min, max);
+                }
+            }
+        });
+}
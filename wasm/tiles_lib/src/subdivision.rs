@@ -0,0 +1,188 @@
+use rayon::prelude::*;
+use crate::border::{add_padding, BorderMode};
+use crate::hash::{pixel_hash, unit_float};
+
+// Kernel divided by its sum. The length must be odd so the kernel has a center tap.
+pub fn normalize_kernel(kernel: &[f32]) -> Result<Vec<f32>, String> {
+    if kernel.len().is_multiple_of(2) {
+        return Err("Kernel length must be odd".to_string());
+    }
+    let sum: f32 = kernel.iter().sum();
+    if !sum.is_finite() || sum == 0.0 {
+        return Err("Kernel must have a finite, non-zero sum".to_string());
+    }
+    Ok(kernel.iter().map(|k| k / sum).collect())
+}
+
+// Separable blur with a normalized odd-length kernel, applied to rows and then to columns.
+// Only pixels whose whole window lies inside the data are computed,
+// so the result is smaller by the kernel radius on every side.
+fn blur_data(data: &[f32], width: usize, height: usize, k: &[f32]) -> Vec<f32> {
+    let radius = k.len() / 2;
+    let result_width = width - 2 * radius;
+    let result_height = height - 2 * radius;
+
+    // Horizontal pass
+    let mut temp = vec![0.0; result_width * height];
+    temp.par_chunks_exact_mut(result_width)
+        .zip(data.par_chunks_exact(width))
+        .for_each(|(row, src)| {
+            for (value, window) in row.iter_mut().zip(src.windows(k.len())) {
+                *value = k.iter().zip(window).map(|(w, v)| w * v).sum();
+            }
+        });
+
+    // Vertical pass
+    let mut result = vec![0.0; result_width * result_height];
+    result.par_chunks_exact_mut(result_width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = k.iter()
+                    .enumerate()
+                    .map(|(j, w)| w * temp[(y + j) * result_width + x])
+                    .sum();
+            }
+        });
+
+    result
+}
+
+#[derive(Clone, Copy)]
+pub enum Distribution {
+    Uniform,
+    Gaussian,
+    // Pure smooth refinement without random displacement
+    None,
+}
+
+impl Distribution {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "uniform" => Some(Self::Uniform),
+            "gaussian" => Some(Self::Gaussian),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    // Maps a hash to a jitter with zero mean.
+    // Uniform is in [-0.5, 0.5), Gaussian has the same standard deviation (1 / sqrt(12)).
+    fn sample(&self, hash: u64) -> f32 {
+        match self {
+            Self::Uniform => unit_float(hash) - 0.5,
+            Self::Gaussian => {
+                // Box-Muller transform using the upper and lower halves of the hash
+                let u1 = 1.0 - unit_float(hash);
+                let u2 = unit_float(hash << 32);
+                let r = (-2.0 * u1.ln()).sqrt();
+                r * (2.0 * std::f32::consts::PI * u2).cos() / 12f32.sqrt()
+            }
+            Self::None => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Displacement {
+    // Displacement range factor. 0.1259 (-9.0dB for fBM noise) is the default.
+    pub roughness: f32,
+    pub distribution: Distribution,
+    // Factor applied to the range at every level, e.g. 2^-H for fractional Brownian motion. 1.0 disables decay.
+    pub decay: f32,
+}
+
+// Hash of the seed, the subdivision level and the global vertex coordinate,
+// so the result does not depend on how the map is split into tiles.
+fn vertex_hash(seed: u32, level: u32, x: i64, y: i64) -> u64 {
+    pixel_hash(((seed as u64) << 32) | level as u64, x, y)
+}
+
+// (offset_x, offset_y) is the position of the first vertex in the global vertex grid of this level.
+#[allow(clippy::too_many_arguments)]
+fn subdivide(elevation: &[f32], width: usize, height: usize, blured_elevation: &[f32], padding: usize, displacement: &Displacement, seed: u32, level: u32, offset_x: i64, offset_y: i64) -> Result<Vec<f32>, String> {
+    let blured_elev_width = width + padding * 2;
+
+    if elevation.len() != width * height || blured_elevation.len() != blured_elev_width * (height + padding * 2) {
+        return Err("Invalid data size".to_string());
+    }
+
+    let vertex_width = width + 1;
+    let vertex_height = height + 1;
+    let result_width = width * 2;
+    let mut elevation_vertex = vec![0.0; vertex_width * vertex_height];
+    let mut result = vec![0.0; result_width * height * 2];
+
+    let mut window_buffer = [[0.0; 4]; 4];
+    let roughness = displacement.roughness * displacement.decay.powi(level as i32);
+
+    let calculate_vertex_elevation = |window: &[[f32; 4]; 4], x: usize, y: usize| -> f32 {
+        let s0 = (window[1][1] - window[0][0]) / 2.0 + window[1][1];
+        let s1 = (window[1][2] - window[0][3]) / 2.0 + window[1][2];
+        let s2 = (window[2][1] - window[3][0]) / 2.0 + window[2][1];
+        let s3 = (window[2][2] - window[3][3]) / 2.0 + window[2][2];
+
+        let reference = (s0 + s1 + s2 + s3) / 4.0;
+        let range = (s0.max(s1).max(s2).max(s3) - s0.min(s1).min(s2).min(s3)) * roughness;
+        let hash = vertex_hash(seed, level, offset_x + x as i64, offset_y + y as i64);
+        reference + (displacement.distribution.sample(hash) * range)
+    };
+
+    for y in 0..vertex_height {
+        let row_offset0 = y * blured_elev_width;
+        let row_offset1 = (y + 1) * blured_elev_width;
+        let row_offset2 = (y + 2) * blured_elev_width;
+        let row_offset3 = (y + 3) * blured_elev_width;
+
+        for x in 0..vertex_width {
+            window_buffer[0].copy_from_slice(&blured_elevation[row_offset0 + x..row_offset0 + x + 4]);
+            window_buffer[1].copy_from_slice(&blured_elevation[row_offset1 + x..row_offset1 + x + 4]);
+            window_buffer[2].copy_from_slice(&blured_elevation[row_offset2 + x..row_offset2 + x + 4]);
+            window_buffer[3].copy_from_slice(&blured_elevation[row_offset3 + x..row_offset3 + x + 4]);
+
+            elevation_vertex[y * vertex_width + x] = calculate_vertex_elevation(&window_buffer, x, y);
+        }
+    }
+
+    for y in 0..height {
+        let core_offset = y * width;
+        let vertex_offset0 = y * vertex_width;
+        let vertex_offset1 = (y + 1) * vertex_width;
+        let result_offset0 = y * 2 * result_width;
+        let result_offset1 = (y * 2 + 1) * result_width;
+
+        for x in 0..width {
+            result[result_offset0 + x * 2] = (elevation_vertex[vertex_offset0 + x] + elevation[core_offset + x]) / 2.0;
+            result[result_offset0 + x * 2 + 1] = (elevation_vertex[vertex_offset0 + x + 1] + elevation[core_offset + x]) / 2.0;
+            result[result_offset1 + x * 2] = (elevation_vertex[vertex_offset1 + x] + elevation[core_offset + x]) / 2.0;
+            result[result_offset1 + x * 2 + 1] = (elevation_vertex[vertex_offset1 + x + 1] + elevation[core_offset + x]) / 2.0;
+        }
+    }
+
+    Ok(result)
+}
+
+// Size after `count` subdivisions, or None on overflow
+pub fn subdivided_size(width: usize, height: usize, count: usize) -> Option<(usize, usize)> {
+    let factor = u32::try_from(count).ok().and_then(|count| 1usize.checked_shl(count))?;
+    Some((width.checked_mul(factor)?, height.checked_mul(factor)?))
+}
+
+// Subdivides `count` times. (offset_x, offset_y) is the position of the input in a larger map, in input pixels.
+// `border` extends the map at every level before it is blurred, so the blur also reads it.
+#[allow(clippy::too_many_arguments)]
+pub fn subdivide_by_gradient(elevation: &[f32], width: usize, height: usize, kernel: &[f32], count: usize, displacement: &Displacement, border: &BorderMode, seed: u32, offset_x: i64, offset_y: i64) -> Result<Vec<f32>, String> {
+    let kernel = normalize_kernel(kernel)?;
+    let padding = 2;
+    let radius = kernel.len() / 2;
+    let mut result = elevation.to_vec();
+
+    for level in 0..count {
+        let (w, h) = (width << level, height << level);
+        let padded_elevation = add_padding(&result, w, h, padding + radius, border);
+        let blured_elevation = blur_data(&padded_elevation, w + 2 * (padding + radius), h + 2 * (padding + radius), &kernel);
+        result = subdivide(&result, w, h, &blured_elevation, padding, displacement, seed, level as u32, offset_x << level, offset_y << level)?;
+    }
+
+    Ok(result)
+}
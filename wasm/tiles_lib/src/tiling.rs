@@ -0,0 +1,126 @@
+use rayon::prelude::*;
+
+// Splits a width x height map into divisions_x x divisions_y cells.
+// Every tile is its cell extended by `halo` pixels on each side, clamped to the map.
+pub struct TileLayout {
+    pub width: usize,
+    pub height: usize,
+    pub divisions_x: usize,
+    pub divisions_y: usize,
+    pub halo: usize,
+}
+
+#[derive(Clone, Copy)]
+pub struct TileRect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl TileRect {
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+}
+
+// Start of cell `i` of `divisions` cells over `len` pixels
+fn cell_start(i: usize, divisions: usize, len: usize) -> usize {
+    i * len / divisions
+}
+
+// Cross-fade weight of position `x` for a tile whose cell is [c0, c1).
+// Neighbouring cells fade over [boundary - halo / 2, boundary + halo / 2),
+// so the weights of all tiles add up to 1 and the outer half of each halo is never used.
+fn fade_weight(x: usize, c0: usize, c1: usize, len: usize, halo: usize) -> f32 {
+    if halo == 0 {
+        return if x >= c0 && x < c1 { 1.0 } else { 0.0 };
+    }
+
+    let p = x as f32 + 0.5;
+    let half = halo as f32 / 2.0;
+    let low = if c0 == 0 { 1.0 } else { ((p - (c0 as f32 - half)) / halo as f32).clamp(0.0, 1.0) };
+    let high = if c1 == len { 1.0 } else { (((c1 as f32 + half) - p) / halo as f32).clamp(0.0, 1.0) };
+    low * high
+}
+
+impl TileLayout {
+    pub fn min_cell_size(&self) -> usize {
+        (self.width / self.divisions_x).min(self.height / self.divisions_y)
+    }
+
+    fn cell(&self, col: usize, row: usize) -> TileRect {
+        TileRect {
+            x0: cell_start(col, self.divisions_x, self.width),
+            y0: cell_start(row, self.divisions_y, self.height),
+            x1: cell_start(col + 1, self.divisions_x, self.width),
+            y1: cell_start(row + 1, self.divisions_y, self.height),
+        }
+    }
+
+    pub fn tile_rect(&self, col: usize, row: usize) -> TileRect {
+        let cell = self.cell(col, row);
+        TileRect {
+            x0: cell.x0.saturating_sub(self.halo),
+            y0: cell.y0.saturating_sub(self.halo),
+            x1: (cell.x1 + self.halo).min(self.width),
+            y1: (cell.y1 + self.halo).min(self.height),
+        }
+    }
+
+    // Tiles in row-major order
+    pub fn split(&self, data: &[f32]) -> Vec<Vec<f32>> {
+        (0..self.divisions_x * self.divisions_y)
+            .into_par_iter()
+            .map(|i| {
+                let rect = self.tile_rect(i % self.divisions_x, i / self.divisions_x);
+                let mut tile = Vec::with_capacity(rect.width() * rect.height());
+                for y in rect.y0..rect.y1 {
+                    tile.extend_from_slice(&data[y * self.width + rect.x0..y * self.width + rect.x1]);
+                }
+                tile
+            })
+            .collect()
+    }
+
+    // Inverse of split. The halos of neighbouring tiles are cross-faded.
+    pub fn merge(&self, tiles: &[&[f32]]) -> Vec<f32> {
+        let mut result = vec![0.0; self.width * self.height];
+
+        result.par_chunks_exact_mut(self.width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for tile_row in 0..self.divisions_y {
+                    let rect = self.tile_rect(0, tile_row);
+                    if y < rect.y0 || y >= rect.y1 {
+                        continue;
+                    }
+                    let cell = self.cell(0, tile_row);
+                    let wy = fade_weight(y, cell.y0, cell.y1, self.height, self.halo);
+                    if wy == 0.0 {
+                        continue;
+                    }
+
+                    for tile_col in 0..self.divisions_x {
+                        let rect = self.tile_rect(tile_col, tile_row);
+                        let cell = self.cell(tile_col, tile_row);
+                        let tile = tiles[tile_row * self.divisions_x + tile_col];
+                        let tile_offset = (y - rect.y0) * rect.width();
+
+                        for (value, x) in row[rect.x0..rect.x1].iter_mut().zip(rect.x0..) {
+                            let w = wy * fade_weight(x, cell.x0, cell.x1, self.width, self.halo);
+                            if w > 0.0 {
+                                *value += w * tile[tile_offset + x - rect.x0];
+                            }
+                        }
+                    }
+                }
+            });
+
+        result
+    }
+}